@@ -0,0 +1,118 @@
+#ifndef BLACKMARLIN_H
+#define BLACKMARLIN_H
+
+/* Generated with cbindgen from src/ffi.rs, regenerate with `BLESS=1 cargo test --test ffi` */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Size of [BmInfo::pv] including the null terminator
+#define BM_PV_LEN 1024
+
+// Engine handle created by [bm_engine_new]
+typedef struct BmEngine BmEngine;
+
+// Search limits, negative values leave a limit unset
+typedef struct BmLimits {
+  int32_t depth;
+  int64_t nodes;
+  int64_t move_time_ms;
+  int64_t w_time_ms;
+  int64_t b_time_ms;
+  int64_t w_inc_ms;
+  int64_t b_inc_ms;
+  int32_t moves_to_go;
+  int32_t mate;
+  // Non zero to search until [bm_engine_stop] is called
+  int32_t infinite;
+} BmLimits;
+
+// Progress of a single PV line
+typedef struct BmInfo {
+  uint32_t depth;
+  uint32_t sel_depth;
+  // 1 based index of the PV line
+  uint32_t multi_pv;
+  // Centipawns, or moves until mate if `is_mate` is non zero
+  int32_t score;
+  int32_t is_mate;
+  // 0 for an exact score, 1 for a lower bound and 2 for an upper bound
+  int32_t bound;
+  uint64_t nodes;
+  uint64_t time_ms;
+  // Permille of the transposition table in use
+  uint32_t hashfull;
+  // Space separated UCI moves
+  char pv[BM_PV_LEN];
+} BmInfo;
+
+// Limits with every limit unset
+struct BmLimits bm_limits_default(void);
+
+// Creates an engine set to the start position, free it with [bm_engine_free]
+// - Returns null if the engine can't be created
+struct BmEngine *bm_engine_new(void);
+
+// Stops any running search and frees the engine
+//
+// # Safety
+// `engine` must be null or a pointer returned by [bm_engine_new] that hasn't been freed
+void bm_engine_free(struct BmEngine *engine);
+
+// Enables Chess960 castling notation and Shredder FENs, applies to following calls
+//
+// # Safety
+// `engine` must be a valid engine
+int32_t bm_engine_set_chess960(struct BmEngine *engine, int32_t chess960);
+
+// Sets the position, waits for a running search to finish
+//
+// # Safety
+// `engine` must be a valid engine and `fen` a null terminated string
+int32_t bm_engine_set_fen(struct BmEngine *engine, const char *fen);
+
+// Plays a move in UCI notation, waits for a running search to finish
+//
+// # Safety
+// `engine` must be a valid engine and `uci_move` a null terminated string
+int32_t bm_engine_make_move(struct BmEngine *engine, const char *uci_move);
+
+// Starts searching the current position in the background
+// - Fails if a search is already running or the position has no legal moves
+//
+// # Safety
+// `engine` must be a valid engine and `limits` null or a valid pointer, null searches without limits
+int32_t bm_engine_start_search(struct BmEngine *engine,
+                               const struct BmLimits *limits);
+
+// Requests the running search to stop, the best move is still available
+//
+// # Safety
+// `engine` must be a valid engine
+int32_t bm_engine_stop(struct BmEngine *engine);
+
+// Returns 1 if a search is running, otherwise 0
+//
+// # Safety
+// `engine` must be a valid engine
+int32_t bm_engine_is_searching(const struct BmEngine *engine);
+
+// Writes the oldest unread progress report into `info`
+// - Returns 1 if a report was written and 0 if there was none
+//
+// # Safety
+// `engine` must be a valid engine and `info` a valid pointer
+int32_t bm_engine_poll_info(struct BmEngine *engine, struct BmInfo *info);
+
+// Waits for the last search to finish and writes its best move into `buffer`
+// - An infinite search is stopped first
+// - Fails if no search was started, the search failed or the buffer is too small
+//
+// # Safety
+// `engine` must be a valid engine and `buffer` valid for `len` bytes
+int32_t bm_engine_best_move(struct BmEngine *engine, char *buffer, size_t len);
+
+#endif /* BLACKMARLIN_H */
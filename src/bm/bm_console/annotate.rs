@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use cozy_chess::{Board, Color, GameStatus, Move};
+
+use crate::bm::{
+    bm_runner::{
+        ab_runner::{self, AbRunner},
+        config::{NoInfo, Run},
+        time::{TimeManagementInfo, TimeManager},
+    },
+    bm_util::{
+        eval::Evaluation,
+        pgn::{self, PgnGame, PgnMove},
+    },
+};
+
+/// Minimum expected score drops of an inaccuracy, a mistake and a blunder
+const DROP_THRESHOLDS: [f32; 3] = [0.05, 0.10, 0.15];
+/// `?!`, `?` and `??`
+const DROP_NAGS: [u8; 3] = [6, 2, 4];
+const DROP_NAMES: [&str; 3] = ["Inaccuracy", "Mistake", "Blunder"];
+
+pub struct AnnotateOptions {
+    pub limits: Vec<TimeManagementInfo>,
+    pub threads: u8,
+    pub hash: usize,
+}
+
+/// Inaccuracies, mistakes and blunders of one side
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorCounts(pub [u32; 3]);
+
+/// Search result of a position of the game
+struct Analysis {
+    eval: Evaluation,
+    depth: u32,
+    pv: Vec<Move>,
+}
+
+/// Expected score of the side to move
+fn expected_score(eval: Evaluation) -> f32 {
+    let (win, _, draw) = ab_runner::to_wld(eval);
+    (win as f32 + draw as f32 * 0.5) / 1000.0
+}
+
+/// Searches every position of the main line and annotates each move with the eval
+/// of the mover, flagging moves that lose expected score compared to the best move
+/// - Flagged moves get a NAG, a comment and the engine's PV as a variation
+pub fn annotate(
+    game: &mut PgnGame,
+    runner: &mut AbRunner,
+    time_manager: &TimeManager,
+    options: &AnnotateOptions,
+) -> [ErrorCounts; Color::NUM] {
+    runner.new_game();
+    runner.set_board(game.board.clone());
+    let mut boards = vec![];
+    let mut analyses = vec![];
+    for ply in 0..=game.moves.len() {
+        let board = runner.get_board().clone();
+        let analysis = match board.status() {
+            GameStatus::Ongoing => {
+                time_manager.initiate(&board, &options.limits);
+                let (_, eval, depth, _) = runner.search::<Run>(Arc::new(NoInfo));
+                time_manager.clear();
+                Some(Analysis {
+                    eval: ab_runner::reported_eval(&board, eval),
+                    depth,
+                    pv: runner.pv().to_vec(),
+                })
+            }
+            _ => None,
+        };
+        analyses.push(analysis);
+        if let Some(pgn_move) = game.moves.get(ply) {
+            runner.make_move(pgn_move.make_move);
+        }
+        boards.push(board);
+    }
+
+    let mut counts = [ErrorCounts::default(); Color::NUM];
+    for (ply, pgn_move) in game.moves.iter_mut().enumerate() {
+        let board = &boards[ply];
+        let child = &boards[ply + 1];
+        let Some(analysis) = &analyses[ply] else {
+            continue;
+        };
+        pgn_move.nags.clear();
+        let (played_score, comment) = match (&analyses[ply + 1], child.status()) {
+            (Some(reply), _) => (
+                1.0 - expected_score(reply.eval),
+                Some(pgn::eval_comment(-reply.eval, reply.depth)),
+            ),
+            (None, GameStatus::Won) => (1.0, None),
+            (None, _) => (0.5, None),
+        };
+        pgn_move.comment = comment;
+
+        let best_move = analysis.pv.first().copied();
+        let drop = expected_score(analysis.eval) - played_score;
+        let Some(severity) = DROP_THRESHOLDS
+            .iter()
+            .rposition(|&threshold| drop >= threshold)
+        else {
+            continue;
+        };
+        if best_move.is_none() || best_move == Some(pgn_move.make_move) {
+            continue;
+        }
+        counts[board.side_to_move() as usize].0[severity] += 1;
+        pgn_move.nags.push(DROP_NAGS[severity]);
+        let comment = pgn_move.comment.get_or_insert_with(String::new);
+        if !comment.is_empty() {
+            comment.push(' ');
+        }
+        comment.push_str(DROP_NAMES[severity]);
+        comment.push('.');
+        let mut variation = analysis
+            .pv
+            .iter()
+            .map(|&make_move| PgnMove::new(make_move))
+            .collect::<Vec<_>>();
+        variation[0].comment = Some(pgn::eval_comment(analysis.eval, analysis.depth));
+        pgn_move.variations = vec![variation];
+    }
+    game.set_tag("Annotator", "Black Marlin");
+    counts
+}
+
+/// Annotates every game of a PGN collection, games that fail to parse are skipped
+pub fn annotate_games(input: &str, options: &AnnotateOptions) -> Vec<PgnGame> {
+    let time_manager = Arc::new(TimeManager::new());
+    let mut runner = AbRunner::new(Board::default(), time_manager.clone());
+    runner.hash(options.hash);
+    runner.set_threads(options.threads);
+
+    let mut games = vec![];
+    for (index, game) in PgnGame::parse_all(input, false).into_iter().enumerate() {
+        let mut game = match game {
+            Ok(game) => game,
+            Err(err) => {
+                println!("game {}: {}", index + 1, err);
+                continue;
+            }
+        };
+        let counts = annotate(&mut game, &mut runner, &time_manager, options);
+        let side = |color: Color| {
+            let [inaccuracies, mistakes, blunders] = counts[color as usize].0;
+            format!(
+                "{} {}/{}/{}",
+                game.tag(&format!("{:?}", color)).unwrap_or("?"),
+                inaccuracies,
+                mistakes,
+                blunders
+            )
+        };
+        println!(
+            "game {}: {} moves, {}, {} (inaccuracies/mistakes/blunders)",
+            index + 1,
+            game.moves.len(),
+            side(Color::White),
+            side(Color::Black)
+        );
+        games.push(game);
+    }
+    games
+}
+
+#[test]
+fn mate_scores() {
+    assert_eq!(expected_score(Evaluation::new_checkmate(3)), 1.0);
+    assert_eq!(expected_score(Evaluation::new_checkmate(-3)), 0.0);
+    assert_eq!(expected_score(-Evaluation::new_checkmate(2)), 0.0);
+    assert!((expected_score(Evaluation::new(0)) - 0.5).abs() < 0.01);
+}
@@ -0,0 +1,293 @@
+use std::sync::Arc;
+
+use cozy_chess::{Board, Color};
+
+use crate::bm::{
+    bm_runner::{ab_runner::AbRunner, time::TimeManager},
+    bm_util::{epd::Epd, xor_shift::XorShift},
+    nnue::Nnue,
+};
+
+use super::self_play::{self, GameLimits};
+
+/// Sequential probability ratio test between two logistic Elo differences
+#[derive(Debug, Clone, Copy)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    /// False positive rate
+    pub alpha: f64,
+    /// False negative rate
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// Log-likelihood ratio bounds, H0 is accepted below the first and H1 above the second
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+}
+
+/// Game results from the perspective of the first engine
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchResults {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+fn elo(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+impl MatchResults {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn add(&mut self, score: f64) {
+        match score {
+            _ if score > 0.5 => self.wins += 1,
+            _ if score < 0.5 => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+
+    fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 * 0.5) / self.games() as f64
+    }
+
+    /// Variance of the score of a single game
+    fn variance(&self) -> f64 {
+        let score = self.score();
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / self.games() as f64
+    }
+
+    pub fn elo(&self) -> f64 {
+        elo(self.score())
+    }
+
+    /// Half width of the 95% confidence interval of [elo](Self::elo)
+    /// - Infinite while the interval of the score isn't within 0 to 1
+    pub fn elo_error(&self) -> f64 {
+        let score = self.score();
+        let deviation = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        let (low, high) = (score - deviation, score + deviation);
+        if low <= 0.0 || high >= 1.0 {
+            return f64::INFINITY;
+        }
+        (elo(high) - elo(low)) / 2.0
+    }
+
+    /// Log-likelihood ratio of H1 against H0 using the trinomial normal approximation
+    pub fn llr(&self, sprt: &Sprt) -> f64 {
+        let variance = self.variance();
+        if self.games() == 0 || variance == 0.0 {
+            return 0.0;
+        }
+        let (score_0, score_1) = (expected_score(sprt.elo0), expected_score(sprt.elo1));
+        self.games() as f64 * (score_1 - score_0) * (2.0 * self.score() - score_0 - score_1)
+            / (2.0 * variance)
+    }
+}
+
+/// Engine configuration of one side of the match
+#[derive(Debug, Clone, Default)]
+pub struct Contestant {
+    pub net: Option<String>,
+    /// Tunable parameters that differ from the current values
+    #[cfg(feature = "tune")]
+    pub params: Vec<(String, i32)>,
+}
+
+pub struct MatchOptions {
+    /// EPD file with one opening per line, random openings are played if missing
+    pub openings: Option<String>,
+    /// Game pairs, each opening is played once with either color
+    pub pairs: u32,
+    pub limits: GameLimits,
+    pub contestants: [Contestant; 2],
+    pub sprt: Sprt,
+    pub seed: u64,
+}
+
+/// Reads the positions of an EPD file, operations are ignored
+fn read_openings(path: &str) -> std::io::Result<Vec<Board>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut openings = vec![];
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        match Epd::parse(line) {
+            Ok(epd) => openings.push(epd.board),
+            Err(err) => println!("skipping opening {}: {}", line, err),
+        }
+    }
+    Ok(openings)
+}
+
+fn create_engine(contestant: &Contestant, time_manager: &Arc<TimeManager>) -> Option<AbRunner> {
+    let mut engine = AbRunner::new(Board::default(), time_manager.clone());
+    if let Some(path) = &contestant.net {
+        match Nnue::from_file(path) {
+            Ok(nnue) => engine.set_network(nnue),
+            Err(err) => {
+                println!("failed to load {}: {}", path, err);
+                return None;
+            }
+        }
+    }
+    Some(engine)
+}
+
+/// Complete parameter sets of both contestants, [None] if a name is unknown
+#[cfg(feature = "tune")]
+fn param_sets(contestants: &[Contestant; 2]) -> Option<[Vec<i32>; 2]> {
+    use crate::bm::bm_util::tunables::TUNABLES;
+
+    let current = TUNABLES
+        .iter()
+        .map(|tunable| tunable.get())
+        .collect::<Vec<_>>();
+    let mut sets = [current.clone(), current];
+    for (set, contestant) in sets.iter_mut().zip(contestants) {
+        for (name, value) in &contestant.params {
+            let Some(index) = TUNABLES
+                .iter()
+                .position(|tunable| tunable.name.eq_ignore_ascii_case(name))
+            else {
+                println!("unknown parameter {}", name);
+                return None;
+            };
+            set[index] = *value;
+        }
+    }
+    Some(sets)
+}
+
+/// Plays game pairs between the two contestants until the SPRT
+/// reaches a decision or all pairs have been played
+pub fn run_match(options: &MatchOptions) -> MatchResults {
+    let mut results = MatchResults::default();
+    let time_manager = Arc::new(TimeManager::new());
+    let (Some(mut engine_0), Some(mut engine_1)) = (
+        create_engine(&options.contestants[0], &time_manager),
+        create_engine(&options.contestants[1], &time_manager),
+    ) else {
+        return results;
+    };
+    #[cfg(feature = "tune")]
+    let Some(param_sets) = param_sets(&options.contestants) else {
+        return results;
+    };
+    #[cfg(feature = "tune")]
+    let mut prepare = |index: usize, engine: &mut AbRunner| {
+        use crate::bm::bm_util::tunables::TUNABLES;
+
+        for (tunable, &value) in TUNABLES.iter().zip(&param_sets[index]) {
+            tunable.set(value);
+        }
+        engine.reload_params();
+    };
+    #[cfg(not(feature = "tune"))]
+    let mut prepare = |_: usize, _: &mut AbRunner| {};
+    let openings = match &options.openings {
+        Some(path) => match read_openings(path) {
+            Ok(openings) if !openings.is_empty() => openings,
+            Ok(_) => {
+                println!("no openings in {}", path);
+                return results;
+            }
+            Err(err) => {
+                println!("failed to read {}: {}", path, err);
+                return results;
+            }
+        },
+        None => vec![],
+    };
+
+    let mut rng = XorShift::new(options.seed);
+    let (lower, upper) = options.sprt.bounds();
+    for pair in 0..options.pairs as usize {
+        let opening = match openings.is_empty() {
+            true => self_play::random_opening(&mut rng),
+            false => openings[pair % openings.len()].clone(),
+        };
+        for color in [Color::White, Color::Black] {
+            let score = self_play::play_game(
+                [&mut engine_0, &mut engine_1],
+                color,
+                &time_manager,
+                options.limits,
+                &opening,
+                &mut prepare,
+            );
+            results.add(score);
+        }
+        let llr = results.llr(&options.sprt);
+        println!(
+            "games {}: +{} ={} -{}, elo {:.1} +- {:.1}, llr {:.2} ({:.2}, {:.2})",
+            results.games(),
+            results.wins,
+            results.draws,
+            results.losses,
+            results.elo(),
+            results.elo_error(),
+            llr,
+            lower,
+            upper
+        );
+        if llr >= upper {
+            println!("H1 accepted");
+            break;
+        }
+        if llr <= lower {
+            println!("H0 accepted");
+            break;
+        }
+    }
+    results
+}
+
+#[test]
+fn match_statistics() {
+    let results = MatchResults {
+        wins: 60,
+        draws: 20,
+        losses: 20,
+    };
+    assert!((results.elo() - 147.19).abs() < 0.01);
+    assert!(results.elo_error() > 0.0 && results.elo_error() < results.elo());
+
+    let sprt = Sprt {
+        elo0: 0.0,
+        elo1: 5.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+    let (lower, upper) = sprt.bounds();
+    assert!((upper - 2.944).abs() < 0.001 && (lower + 2.944).abs() < 0.001);
+    assert!(results.llr(&sprt) > 0.0 && results.llr(&sprt) < upper);
+    let decisive = MatchResults {
+        wins: 600,
+        draws: 200,
+        losses: 200,
+    };
+    assert!(decisive.llr(&sprt) > upper);
+
+    let even = MatchResults {
+        wins: 30,
+        draws: 40,
+        losses: 30,
+    };
+    assert!(even.elo().abs() < 1e-9);
+    assert!(even.llr(&sprt) < 0.0);
+}
@@ -0,0 +1,164 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use cozy_chess::{Board, GameStatus, Move};
+
+use crate::bm::{
+    bm_runner::{
+        ab_runner::AbRunner,
+        config::{GuiInfo, Run, SearchInfo},
+        time::{TimeManagementInfo, TimeManager},
+    },
+    bm_util::{epd::Epd, san, t_table::Bounds},
+    uci,
+};
+
+pub struct SuiteOptions {
+    pub limits: Vec<TimeManagementInfo>,
+    pub threads: u8,
+    pub hash: usize,
+}
+
+/// Best move of every completed iteration with the time it was reported at
+struct BestMoves {
+    board: Board,
+    moves: Mutex<Vec<(Duration, Move)>>,
+}
+
+impl GuiInfo for BestMoves {
+    fn print_info(&self, info: &SearchInfo) {
+        if info.multi_pv != 1 || info.bounds != Bounds::Exact {
+            return;
+        }
+        if let Some(&(mut make_move)) = info.pv.first() {
+            uci::convert_move(&mut make_move, &self.board, false);
+            self.moves.lock().unwrap().push((info.elapsed, make_move));
+        }
+    }
+
+    fn print_cur_move(&self, _: u32, _: Move, _: usize) {}
+}
+
+/// STS style points from a `c0` string such as `"Qd1+=10, Qc6=3"`
+fn move_points(board: &Board, points: &str) -> Option<Vec<(Move, u32)>> {
+    points
+        .split(',')
+        .map(|entry| {
+            let (san, points) = entry.trim().rsplit_once('=')?;
+            let make_move = san::parse_san(board, san).ok()?;
+            Some((make_move, points.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Searches every position of an EPD suite and reports whether the best move
+/// matches `bm` and avoids `am`, the time it was first found and kept and STS points
+/// - Positions with neither `bm` nor `am` are reported as unscored and aren't counted
+/// - Positions where the game is over, such as checkmate and stalemate, are skipped
+pub fn run_suite(lines: impl Iterator<Item = String>, options: &SuiteOptions) {
+    let time_manager = Arc::new(TimeManager::new());
+    let mut runner = AbRunner::new(Board::default(), time_manager.clone());
+    runner.hash(options.hash);
+    runner.set_threads(options.threads);
+
+    let mut positions = 0;
+    let mut scored_count = 0;
+    let mut solved_count = 0;
+    let mut solved_time = Duration::ZERO;
+    let mut points = 0;
+    let mut max_points = 0;
+    for (index, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let epd = match Epd::parse(&line) {
+            Ok(epd) => epd,
+            Err(err) => {
+                println!("line {}: {}", index + 1, err);
+                continue;
+            }
+        };
+        if epd.board.status() != GameStatus::Ongoing {
+            println!("line {}: game over, skipped", index + 1);
+            continue;
+        }
+        let (best_moves, avoid_moves) = match (epd.moves("bm"), epd.moves("am")) {
+            (Ok(best_moves), Ok(avoid_moves)) => (best_moves, avoid_moves),
+            (Err(err), _) | (_, Err(err)) => {
+                println!("line {}: {}", index + 1, err);
+                continue;
+            }
+        };
+        let move_points = epd
+            .string("c0")
+            .and_then(|c0| move_points(&epd.board, c0))
+            .unwrap_or_default();
+        let scored = !best_moves.is_empty() || !avoid_moves.is_empty();
+        let solves = |make_move: Move| {
+            (best_moves.is_empty() || best_moves.contains(&make_move))
+                && !avoid_moves.contains(&make_move)
+        };
+
+        runner.new_game();
+        runner.set_board(epd.board.clone());
+        let progress = Arc::new(BestMoves {
+            board: epd.board.clone(),
+            moves: Mutex::new(vec![]),
+        });
+        let start = Instant::now();
+        time_manager.initiate(&epd.board, &options.limits);
+        let (best_move, _, _, _) = runner.search::<Run>(progress.clone());
+        time_manager.clear();
+        let elapsed = start.elapsed();
+
+        let mut solution_time = elapsed;
+        for &(time, make_move) in progress.moves.lock().unwrap().iter().rev() {
+            if !solves(make_move) {
+                break;
+            }
+            solution_time = time;
+        }
+        positions += 1;
+        let mut result = match (scored, solves(best_move)) {
+            (false, _) => "unscored".to_string(),
+            (true, true) => {
+                scored_count += 1;
+                solved_count += 1;
+                solved_time += solution_time;
+                format!("solved in {} ms", solution_time.as_millis())
+            }
+            (true, false) => {
+                scored_count += 1;
+                "failed".to_string()
+            }
+        };
+        if let Some(max) = move_points.iter().map(|&(_, points)| points).max() {
+            let scored = move_points
+                .iter()
+                .find(|&&(make_move, _)| make_move == best_move)
+                .map_or(0, |&(_, points)| points);
+            points += scored;
+            max_points += max;
+            result += &format!(", {}/{} points", scored, max);
+        }
+        println!(
+            "[#{:>4}] {:<16} {:<8} {}",
+            positions,
+            epd.string("id").unwrap_or("-"),
+            san::to_san(&epd.board, best_move),
+            result
+        );
+    }
+    println!(
+        "solved {}/{}, average time to solution {} ms",
+        solved_count,
+        scored_count,
+        solved_time.as_millis() / solved_count.max(1) as u128
+    );
+    if scored_count < positions {
+        println!("{} positions without bm or am", positions - scored_count);
+    }
+    if max_points > 0 {
+        println!("score {}/{}", points, max_points);
+    }
+}
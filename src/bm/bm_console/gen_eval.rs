@@ -11,21 +11,29 @@ use rand::Rng;
 
 use crate::bm::{
     bm_runner::{
-        ab_runner::AbRunner,
+        ab_runner::{self, AbRunner},
         config::{NoInfo, Run},
         time::{TimeManagementInfo, TimeManager},
     },
-    bm_util::eval::Evaluation,
+    bm_util::{
+        eval::Evaluation,
+        pgn::{self, PgnGame, PgnMove},
+    },
 };
 
 use threadpool::{self, ThreadPool};
 
+/// Plays a game against itself, returning the positions to train on and the game record
 fn play_single(
     engine: &mut AbRunner,
     time_manager: &TimeManager,
     time_management_info: &[TimeManagementInfo],
-) -> Vec<(Board, Evaluation, f32)> {
+) -> (Vec<(Board, Evaluation, f32)>, PgnGame) {
     let mut evals = Vec::new();
+    let mut game = PgnGame::new(Board::default());
+    game.set_tag("Event", "Black Marlin data generation");
+    game.set_tag("White", "Black Marlin");
+    game.set_tag("Black", "Black Marlin");
     engine.set_board(Board::default());
     let mut result = 0.5;
     for ply in 0.. {
@@ -38,7 +46,7 @@ fn play_single(
             cozy_chess::GameStatus::Ongoing => {}
         }
         time_manager.initiate(engine.get_board(), time_management_info);
-        let (mut make_move, eval, _, _) = engine.search::<Run, NoInfo>();
+        let (mut make_move, eval, depth, _) = engine.search::<Run>(Arc::new(NoInfo));
         time_manager.clear();
         let turn = match engine.get_board().side_to_move() {
             cozy_chess::Color::White => 1,
@@ -65,6 +73,12 @@ fn play_single(
                 false
             });
             make_move = moves[rand::thread_rng().gen_range(0..moves.len())];
+            game.moves.push(PgnMove::new(make_move));
+        } else {
+            let mut pgn_move = PgnMove::new(make_move);
+            let reported = ab_runner::reported_eval(&board, eval);
+            pgn_move.comment = Some(pgn::eval_comment(reported, depth));
+            game.moves.push(pgn_move);
         }
         engine.make_move(make_move);
         if engine.get_position().forced_draw(1) {
@@ -72,50 +86,76 @@ fn play_single(
             break;
         }
     }
-    evals
+    game.set_result(result);
+    let evals = evals
         .into_iter()
         .map(|(b, e)| (b, e, result))
-        .collect::<Vec<_>>()
+        .collect::<Vec<_>>();
+    (evals, game)
 }
 
-fn gen_games(duration: Duration, depth: u32) -> Vec<(Board, Evaluation, f32)> {
+/// Generates games for `duration`, the game records are only kept if `log_games` is set
+fn gen_games(
+    duration: Duration,
+    depth: u32,
+    log_games: bool,
+) -> (Vec<(Board, Evaluation, f32)>, Vec<PgnGame>) {
     let start = Instant::now();
     let mut evals = vec![];
+    let mut games = vec![];
     let time_management_options = TimeManagementInfo::MaxDepth(depth);
     let time_manager = Arc::new(TimeManager::new());
     let mut engine_0 = AbRunner::new(Board::default(), time_manager.clone());
     while start.elapsed() < duration {
-        evals.extend(play_single(
-            &mut engine_0,
-            &time_manager,
-            &[time_management_options],
-        ));
+        let (game_evals, game) =
+            play_single(&mut engine_0, &time_manager, &[time_management_options]);
+        evals.extend(game_evals);
+        if log_games {
+            games.push(game);
+        }
         engine_0.new_game();
     }
-    evals
+    (evals, games)
+}
+
+fn append(path: &str, output: &str) {
+    let file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)
+        .unwrap();
+    let mut write = BufWriter::new(file);
+    write.write_all(output.as_bytes()).unwrap();
+    write.flush().unwrap();
 }
 
-pub fn gen_eval(depth: u32, thread_cnt: u32, target_path: &str) {
+/// Generates training data, full games are additionally written to `pgn_path` if given
+pub fn gen_eval(depth: u32, thread_cnt: u32, target_path: &str, pgn_path: Option<&str>) {
     let pool = ThreadPool::new(thread_cnt as usize);
+    let log_games = pgn_path.is_some();
     loop {
         let (tx, rx) = channel();
         for _ in 0..thread_cnt {
             let tx = tx.clone();
             pool.execute(move || {
-                tx.send(gen_games(Duration::from_secs(30), depth)).unwrap();
+                tx.send(gen_games(Duration::from_secs(30), depth, log_games))
+                    .unwrap();
             });
         }
         let mut output = String::new();
-        for (board, eval, wdl) in rx.iter().take(thread_cnt as usize).flatten() {
-            output += &format!("{} | {} | {}\n", &board.to_string(), eval.raw(), wdl);
+        let mut pgn_output = String::new();
+        for (evals, games) in rx.iter().take(thread_cnt as usize) {
+            for (board, eval, wdl) in evals {
+                output += &format!("{} | {} | {}\n", &board.to_string(), eval.raw(), wdl);
+            }
+            for game in games {
+                pgn_output += &format!("{}\n", game);
+            }
+        }
+        append(target_path, &output);
+        if let Some(pgn_path) = pgn_path {
+            append(pgn_path, &pgn_output);
         }
-        let file = OpenOptions::new()
-            .read(true)
-            .append(true)
-            .create(true)
-            .open(target_path)
-            .unwrap();
-        let mut write = BufWriter::new(file);
-        write.write(output.as_bytes()).unwrap();
     }
 }
@@ -1,40 +1,69 @@
 use super::uci::UciAdapter;
+use super::xboard::XBoardAdapter;
 
+mod annotate;
+#[cfg(feature = "data")]
+mod engine_match;
+mod epd_suite;
 #[cfg(feature = "data")]
 mod gen_eval;
 #[cfg(feature = "trace")]
 mod gen_fen;
 #[cfg(feature = "trace")]
 mod grad;
+#[cfg(any(feature = "data", feature = "tune"))]
+mod self_play;
+#[cfg(feature = "tune")]
+mod spsa;
+enum Protocol {
+    Uci(UciAdapter),
+    XBoard(XBoardAdapter),
+}
+
 pub struct BmConsole {
-    uci: UciAdapter,
+    /// Chosen by the first protocol command, xboard if it is `xboard` and UCI otherwise
+    protocol: Option<Protocol>,
 }
 
 impl BmConsole {
     pub fn new() -> Self {
-        Self {
-            uci: UciAdapter::new(),
-        }
+        Self { protocol: None }
     }
 
     pub fn input(&mut self, command: &str) -> bool {
         if command.is_empty() {
             return false;
         }
-        #[cfg(any(feature = "trace", feature = "data"))]
-        if command.starts_with("!") {
-            let (command, options) = Self::parse(&command[1..]);
+        if let Some(command) = command.strip_prefix('!') {
+            let (command, options) = Self::parse(command);
             let command: &str = &command;
             match command {
                 #[cfg(feature = "trace")]
                 "tune" => Self::tune(options),
                 #[cfg(feature = "data")]
                 "data" => Self::data(options),
+                #[cfg(feature = "data")]
+                "match" => Self::engine_match(options),
+                "movegen-check" => Self::movegen_check(options),
+                "nnue-check" => Self::nnue_check(options),
+                "epd" => Self::epd(options),
+                "annotate" => Self::annotate(options),
+                #[cfg(feature = "tune")]
+                "spsa-config" => Self::spsa_config(options),
+                #[cfg(feature = "tune")]
+                "spsa" => Self::spsa(options),
                 _ => {}
             }
             return true;
         }
-        self.uci.input(command)
+        let protocol = self.protocol.get_or_insert_with(|| match command.trim() {
+            "xboard" => Protocol::XBoard(XBoardAdapter::new()),
+            _ => Protocol::Uci(UciAdapter::new()),
+        });
+        match protocol {
+            Protocol::Uci(uci) => uci.input(command),
+            Protocol::XBoard(xboard) => xboard.input(command),
+        }
     }
 
     #[cfg(feature = "data")]
@@ -46,9 +75,256 @@ impl BmConsole {
             options.get("depth").unwrap().parse::<u32>().unwrap(),
             options.get("threads").unwrap().parse::<u32>().unwrap(),
             options.get("path").unwrap(),
+            options.get("pgn").map(String::as_str),
         );
     }
 
+    #[cfg(feature = "data")]
+    fn engine_match(options: Vec<(String, String)>) {
+        use std::collections::HashMap;
+
+        let options = options.into_iter().collect::<HashMap<String, String>>();
+        let get = |name: &str, default: f64| {
+            options
+                .get(name)
+                .map_or(default, |value| value.parse().unwrap())
+        };
+        let limits = Self::game_limits(&options, 10000);
+        let contestant = |side: &str| engine_match::Contestant {
+            net: options.get(&format!("net-{}", side)).cloned(),
+            #[cfg(feature = "tune")]
+            params: options
+                .get(&format!("params-{}", side))
+                .map_or(vec![], |params| {
+                    params
+                        .split(',')
+                        .map(|param| {
+                            let (name, value) = param.split_once('=').unwrap();
+                            (name.trim().to_string(), value.trim().parse().unwrap())
+                        })
+                        .collect()
+                }),
+        };
+        engine_match::run_match(&engine_match::MatchOptions {
+            openings: options.get("openings").cloned(),
+            pairs: (get("games", 1000.0) as u32).div_ceil(2),
+            limits,
+            contestants: [contestant("a"), contestant("b")],
+            sprt: engine_match::Sprt {
+                elo0: get("elo0", 0.0),
+                elo1: get("elo1", 5.0),
+                alpha: get("alpha", 0.05),
+                beta: get("beta", 0.05),
+            },
+            seed: get("seed", 1.0) as u64,
+        });
+    }
+
+    fn movegen_check(options: Vec<(String, String)>) {
+        use std::collections::HashMap;
+
+        use cozy_chess::Board;
+
+        use crate::bm::bm_search::move_gen_check;
+        use crate::bm::uci::bench;
+
+        let options = options.into_iter().collect::<HashMap<String, String>>();
+        let iterations = options
+            .get("iterations")
+            .map_or(16, |iterations| iterations.parse().unwrap());
+        let seed = options.get("seed").map_or(1, |seed| seed.parse().unwrap());
+        let boards: Vec<Board> = match options.get("input") {
+            Some(input_file) => std::fs::read_to_string(input_file)
+                .unwrap()
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.trim().parse().unwrap())
+                .collect(),
+            None => bench::bench_positions().collect(),
+        };
+
+        let board_cnt = boards.len();
+        let errors = move_gen_check::check_positions(boards.into_iter(), iterations, seed);
+        for (fen, err) in &errors {
+            println!("{}: {}", fen, err);
+        }
+        println!("checked {} positions, {} failures", board_cnt, errors.len());
+    }
+
+    fn nnue_check(options: Vec<(String, String)>) {
+        use std::collections::HashMap;
+
+        use crate::bm::nnue::verify;
+
+        let options = options.into_iter().collect::<HashMap<String, String>>();
+        let games = options
+            .get("games")
+            .map_or(100, |games| games.parse().unwrap());
+        let plies = options
+            .get("plies")
+            .map_or(256, |plies| plies.parse().unwrap());
+        let seed = options.get("seed").map_or(1, |seed| seed.parse().unwrap());
+
+        match verify::verify_games(games, plies, seed) {
+            Some(divergence) => println!("{}", divergence),
+            None => println!("checked {} games, no divergence", games),
+        }
+    }
+
+    /// Self-play limits from `-tc base+inc` in seconds, `-movetime` or `-nodes`,
+    /// `default_nodes` per move if none are given
+    #[cfg(any(feature = "data", feature = "tune"))]
+    fn game_limits(
+        options: &std::collections::HashMap<String, String>,
+        default_nodes: u64,
+    ) -> self_play::GameLimits {
+        use std::time::Duration;
+
+        use self_play::GameLimits;
+
+        if let Some(tc) = options.get("tc") {
+            let (base, inc) = tc.split_once('+').unwrap_or((tc, "0"));
+            GameLimits::Clock {
+                base: Duration::from_secs_f64(base.parse().unwrap()),
+                inc: Duration::from_secs_f64(inc.parse().unwrap()),
+            }
+        } else if let Some(move_time) = options.get("movetime") {
+            GameLimits::MoveTime(Duration::from_millis(move_time.parse().unwrap()))
+        } else {
+            GameLimits::Nodes(
+                options
+                    .get("nodes")
+                    .map_or(default_nodes, |nodes| nodes.parse().unwrap()),
+            )
+        }
+    }
+
+    /// Search limits from `-depth`, `-nodes` and `-movetime`, one second per move if none are given
+    fn search_limits(
+        options: &std::collections::HashMap<String, String>,
+    ) -> Vec<crate::bm::bm_runner::time::TimeManagementInfo> {
+        use std::time::Duration;
+
+        use crate::bm::bm_runner::time::TimeManagementInfo;
+
+        let mut limits = vec![];
+        if let Some(depth) = options.get("depth") {
+            limits.push(TimeManagementInfo::MaxDepth(depth.parse().unwrap()));
+        }
+        if let Some(nodes) = options.get("nodes") {
+            limits.push(TimeManagementInfo::MaxNodes(nodes.parse().unwrap()));
+        }
+        if let Some(move_time) = options.get("movetime") {
+            limits.push(TimeManagementInfo::MoveTime(Duration::from_millis(
+                move_time.parse().unwrap(),
+            )));
+        }
+        if limits.is_empty() {
+            limits.push(TimeManagementInfo::MoveTime(Duration::from_secs(1)));
+        }
+        limits
+    }
+
+    fn epd(options: Vec<(String, String)>) {
+        use std::collections::HashMap;
+
+        let options = options.into_iter().collect::<HashMap<String, String>>();
+        let Some(input) = options.get("input") else {
+            println!("missing -input");
+            return;
+        };
+        let content = match std::fs::read_to_string(input) {
+            Ok(content) => content,
+            Err(err) => {
+                println!("failed to read {}: {}", input, err);
+                return;
+            }
+        };
+        epd_suite::run_suite(
+            content.lines().map(str::to_string),
+            &epd_suite::SuiteOptions {
+                limits: Self::search_limits(&options),
+                threads: options
+                    .get("threads")
+                    .map_or(1, |threads| threads.parse().unwrap()),
+                hash: options.get("hash").map_or(16, |hash| hash.parse().unwrap()),
+            },
+        );
+    }
+
+    fn annotate(options: Vec<(String, String)>) {
+        use std::collections::HashMap;
+
+        let options = options.into_iter().collect::<HashMap<String, String>>();
+        let Some(input) = options.get("input") else {
+            println!("missing -input");
+            return;
+        };
+        let content = match std::fs::read_to_string(input) {
+            Ok(content) => content,
+            Err(err) => {
+                println!("failed to read {}: {}", input, err);
+                return;
+            }
+        };
+        let games = annotate::annotate_games(
+            &content,
+            &annotate::AnnotateOptions {
+                limits: Self::search_limits(&options),
+                threads: options
+                    .get("threads")
+                    .map_or(1, |threads| threads.parse().unwrap()),
+                hash: options.get("hash").map_or(16, |hash| hash.parse().unwrap()),
+            },
+        );
+        let output = games
+            .iter()
+            .map(|game| game.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        match options.get("output") {
+            Some(path) => {
+                if let Err(err) = std::fs::write(path, output) {
+                    println!("failed to write {}: {}", path, err);
+                }
+            }
+            None => print!("{}", output),
+        }
+    }
+
+    #[cfg(feature = "tune")]
+    fn spsa_config(options: Vec<(String, String)>) {
+        use crate::bm::bm_util::tunables;
+
+        let format = options
+            .iter()
+            .find(|(key, _)| key == "format")
+            .map_or("openbench", |(_, format)| format.as_str());
+        match format {
+            "openbench" => print!("{}", tunables::openbench_config()),
+            "weather-factory" => print!("{}", tunables::weather_factory_config()),
+            _ => println!(
+                "unknown format {}, expected openbench or weather-factory",
+                format
+            ),
+        }
+    }
+
+    #[cfg(feature = "tune")]
+    fn spsa(options: Vec<(String, String)>) {
+        use std::collections::HashMap;
+
+        let options = options.into_iter().collect::<HashMap<String, String>>();
+        spsa::spsa(&spsa::SpsaOptions {
+            iterations: options
+                .get("iterations")
+                .map_or(1000, |iterations| iterations.parse().unwrap()),
+            limits: Self::game_limits(&options, 5000),
+            log_interval: options.get("log").map_or(10, |log| log.parse().unwrap()),
+            seed: options.get("seed").map_or(1, |seed| seed.parse().unwrap()),
+        });
+    }
+
     #[cfg(feature = "trace")]
     fn tune(options: Vec<(String, String)>) {
         use std::{collections::HashMap, str::FromStr};
@@ -112,7 +388,6 @@ impl BmConsole {
         grad::tune(&traces);
     }
 
-    #[cfg(any(feature = "trace", feature = "data"))]
     fn parse(command: &str) -> (String, Vec<(String, String)>) {
         let split = command.split(' ').collect::<Vec<_>>();
 
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cozy_chess::{Board, Color, GameStatus};
+
+use crate::bm::{
+    bm_runner::{
+        ab_runner::AbRunner,
+        config::{NoInfo, Run},
+        time::{TimeManagementInfo, TimeManager},
+    },
+    bm_util::xor_shift::XorShift,
+};
+
+/// Random plies played from the start position by [random_opening]
+const OPENING_PLIES: usize = 8;
+/// Games longer than this are adjudicated as draws
+const MAX_GAME_PLIES: usize = 400;
+/// Eval from which the side to move is adjudicated to win or lose
+const ADJUDICATE_EVAL: i16 = 1500;
+
+#[derive(Debug, Clone, Copy)]
+pub enum GameLimits {
+    Nodes(u64),
+    MoveTime(Duration),
+    /// Each side starts with `base` and gains `inc` after every move
+    Clock {
+        base: Duration,
+        inc: Duration,
+    },
+}
+
+pub fn random_opening(rng: &mut XorShift) -> Board {
+    loop {
+        let mut board = Board::default();
+        for _ in 0..OPENING_PLIES {
+            let mut moves = vec![];
+            board.generate_moves(|piece_moves| {
+                moves.extend(piece_moves);
+                false
+            });
+            let Some(make_move) = rng.pick(&moves) else {
+                break;
+            };
+            board.play_unchecked(make_move);
+        }
+        if board.status() == GameStatus::Ongoing {
+            return board;
+        }
+    }
+}
+
+/// Plays a game from `opening` and returns the score of `engines[0]`, which plays `color`
+/// - `prepare` is called with the index of the engine to move before each of its searches
+/// - Games are adjudicated on large evals, forced draws, running out of time and length
+pub fn play_game(
+    mut engines: [&mut AbRunner; 2],
+    color: Color,
+    time_manager: &TimeManager,
+    limits: GameLimits,
+    opening: &Board,
+    mut prepare: impl FnMut(usize, &mut AbRunner),
+) -> f64 {
+    for engine in &mut engines {
+        engine.new_game();
+        engine.set_board(opening.clone());
+    }
+    let score_of = |winner: Color| match winner == color {
+        true => 1.0,
+        false => 0.0,
+    };
+    let mut clocks = match limits {
+        GameLimits::Clock { base, .. } => [base; Color::NUM],
+        _ => [Duration::ZERO; Color::NUM],
+    };
+    for _ in 0..MAX_GAME_PLIES {
+        let board = engines[0].get_board().clone();
+        let stm = board.side_to_move();
+        match board.status() {
+            GameStatus::Won => return score_of(!stm),
+            GameStatus::Drawn => return 0.5,
+            GameStatus::Ongoing => {}
+        }
+        let index = (stm != color) as usize;
+        prepare(index, engines[index]);
+        let info = match limits {
+            GameLimits::Nodes(nodes) => vec![TimeManagementInfo::MaxNodes(nodes)],
+            GameLimits::MoveTime(move_time) => vec![TimeManagementInfo::MoveTime(move_time)],
+            GameLimits::Clock { inc, .. } => vec![
+                TimeManagementInfo::WTime(clocks[Color::White as usize]),
+                TimeManagementInfo::BTime(clocks[Color::Black as usize]),
+                TimeManagementInfo::WInc(inc),
+                TimeManagementInfo::BInc(inc),
+            ],
+        };
+        let start = Instant::now();
+        time_manager.initiate(&board, &info);
+        let (make_move, eval, _, _) = engines[index].search::<Run>(Arc::new(NoInfo));
+        time_manager.clear();
+        if let GameLimits::Clock { inc, .. } = limits {
+            let clock = &mut clocks[stm as usize];
+            let elapsed = start.elapsed();
+            if elapsed > *clock {
+                return score_of(!stm);
+            }
+            *clock = *clock - elapsed + inc;
+        }
+        if eval.is_mate() || eval.raw().abs() >= ADJUDICATE_EVAL {
+            return match eval.raw() > 0 {
+                true => score_of(stm),
+                false => score_of(!stm),
+            };
+        }
+        for engine in &mut engines {
+            engine.make_move(make_move);
+        }
+        if engines[0].get_position().forced_draw(1) {
+            return 0.5;
+        }
+    }
+    0.5
+}
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use cozy_chess::{Board, Color};
+
+use crate::bm::{
+    bm_runner::{ab_runner::AbRunner, time::TimeManager},
+    bm_util::{
+        tunables::{self, TUNABLES},
+        xor_shift::XorShift,
+    },
+};
+
+use super::self_play::{self, GameLimits};
+
+const ALPHA: f64 = 0.602;
+const GAMMA: f64 = 0.101;
+/// Learning rate at the end of tuning, as in OpenBench
+const R_END: f64 = 0.002;
+
+pub struct SpsaOptions {
+    pub iterations: u32,
+    pub limits: GameLimits,
+    /// Iterations between logging the current values
+    pub log_interval: u32,
+    pub seed: u64,
+}
+
+/// Applies the given values to the global [tunables] and rebuilds the tables of `engine`
+///
+/// Tunables are shared by every engine in the process so the values of
+/// the engine to move have to be applied before each of its searches
+fn apply(engine: &mut AbRunner, values: &[f64]) {
+    for (tunable, &value) in TUNABLES.iter().zip(values) {
+        tunable.set(value.round() as i32);
+    }
+    engine.reload_params();
+}
+
+/// Tunes the [tunables] with SPSA through self-play game pairs between
+/// the current values shifted up and down by a random perturbation
+///
+/// Games are played one at a time as the tunables are global
+pub fn spsa(options: &SpsaOptions) {
+    let mut rng = XorShift::new(options.seed);
+    let time_manager = Arc::new(TimeManager::new());
+    let mut engine_plus = AbRunner::new(Board::default(), time_manager.clone());
+    let mut engine_minus = AbRunner::new(Board::default(), time_manager.clone());
+    let limits = options.limits;
+
+    let iterations = options.iterations as f64;
+    let stability = iterations * 0.1;
+    let mut values = TUNABLES
+        .iter()
+        .map(|tunable| tunable.get() as f64)
+        .collect::<Vec<_>>();
+    // Perturbation and learning rate scales reaching the step size and R_END at the last iteration
+    let c_scales = TUNABLES
+        .iter()
+        .map(|tunable| tunable.step as f64 * iterations.powf(GAMMA))
+        .collect::<Vec<_>>();
+    let a_scales = TUNABLES
+        .iter()
+        .map(|tunable| R_END * (tunable.step as f64).powi(2) * (stability + iterations).powf(ALPHA))
+        .collect::<Vec<_>>();
+
+    let mut total_score = 0.0;
+    for iteration in 0..options.iterations {
+        let k = iteration as f64 + 1.0;
+        let signs = TUNABLES
+            .iter()
+            .map(|_| match rng.chance(2) {
+                true => 1.0,
+                false => -1.0,
+            })
+            .collect::<Vec<f64>>();
+        let perturbations = c_scales
+            .iter()
+            .zip(&signs)
+            .map(|(c_scale, sign)| c_scale / k.powf(GAMMA) * sign)
+            .collect::<Vec<_>>();
+        let clamped = |shift: f64| {
+            TUNABLES
+                .iter()
+                .zip(&values)
+                .zip(&perturbations)
+                .map(|((tunable, value), perturbation)| {
+                    (value + shift * perturbation).clamp(tunable.min as f64, tunable.max as f64)
+                })
+                .collect::<Vec<_>>()
+        };
+        let (values_plus, values_minus) = (clamped(1.0), clamped(-1.0));
+
+        let opening = self_play::random_opening(&mut rng);
+        let mut score = 0.0;
+        for color in [Color::White, Color::Black] {
+            score += self_play::play_game(
+                [&mut engine_plus, &mut engine_minus],
+                color,
+                &time_manager,
+                limits,
+                &opening,
+                |index, engine| match index {
+                    0 => apply(engine, &values_plus),
+                    _ => apply(engine, &values_minus),
+                },
+            );
+        }
+        // Score difference between the two sides of the pair, in -2 to 2
+        let result = 2.0 * score - 2.0;
+        total_score += score;
+
+        for (index, tunable) in TUNABLES.iter().enumerate() {
+            let learning_rate = a_scales[index] / (stability + k).powf(ALPHA);
+            values[index] = (values[index] + learning_rate * result / perturbations[index])
+                .clamp(tunable.min as f64, tunable.max as f64);
+        }
+
+        if (iteration + 1) % options.log_interval.max(1) == 0 {
+            apply(&mut engine_plus, &values);
+            println!(
+                "iteration {}, plus score {:.3}",
+                iteration + 1,
+                total_score / (2.0 * k)
+            );
+            print!("{}", tunables::openbench_config());
+        }
+    }
+    apply(&mut engine_plus, &values);
+    println!("final values");
+    print!("{}", tunables::openbench_config());
+}
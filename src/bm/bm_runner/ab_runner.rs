@@ -1,10 +1,10 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use cozy_chess::{Board, Color, Move, Piece, Square};
 
-use crate::bm::bm_runner::config::{GuiInfo, NoInfo, SearchMode, SearchStats};
+use crate::bm::bm_runner::config::{GuiInfo, NoInfo, SearchInfo, SearchMode, SearchStats};
 use crate::bm::bm_search::move_entry::MoveEntry;
 use crate::bm::bm_search::search;
 use crate::bm::bm_search::search::Pv;
@@ -12,14 +12,19 @@ use crate::bm::bm_util::eval::Evaluation;
 use crate::bm::bm_util::history::History;
 use crate::bm::bm_util::lookup::LookUp2d;
 use crate::bm::bm_util::position::Position;
-use crate::bm::bm_util::t_table::TranspositionTable;
+use crate::bm::bm_util::t_table::{Bounds, TranspositionTable};
+use crate::bm::bm_util::tunables;
 use crate::bm::bm_util::window::Window;
+use crate::bm::nnue::Nnue;
 use crate::bm::uci;
 
 use super::time::TimeManager;
 
 pub const MAX_PLY: u32 = 128;
 
+/// Fail high/low lines and current root moves are only reported after this much time
+pub const REPORT_DELAY: Duration = Duration::from_millis(3000);
+
 #[derive(Debug, Clone)]
 pub struct NodeCounter {
     node_counters: Vec<Option<Arc<AtomicU64>>>,
@@ -113,7 +118,7 @@ impl SearchStack {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ThreadContext {
     window: Window,
     pub tt_hits: u32,
@@ -132,6 +137,13 @@ pub struct ThreadContext {
     pub abort: bool,
     /// Used for node based time management
     pub root_nodes: [[u64; Square::NUM]; Square::NUM],
+    /// Root moves that have already been reported in previous MultiPV lines
+    pub excluded_moves: Vec<Move>,
+    /// Root moves to restrict the search to, all moves are searched if empty
+    pub search_moves: Vec<Move>,
+    pub chess960: bool,
+    /// Only set for the main thread
+    pub gui_info: Option<Arc<dyn GuiInfo>>,
 }
 
 impl SharedContext {
@@ -142,8 +154,14 @@ impl SharedContext {
         self.time_manager.abort_search(self.start, node_cnt)
     }
 
-    fn abort_deepening(&self, depth: u32, nodes: u64) -> bool {
-        self.time_manager.abort_deepening(self.start, depth, nodes)
+    fn abort_deepening(&self, depth: u32, nodes: u64, eval: Option<Evaluation>) -> bool {
+        self.time_manager
+            .abort_deepening(self.start, depth, nodes, eval)
+    }
+
+    /// Time elapsed since search was started
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
     }
 
     /// Shared transposition table
@@ -199,12 +217,51 @@ fn remove_aggression(eval: Evaluation, scale: i32) -> Evaluation {
     }
 }
 
-fn to_wld(eval: Evaluation) -> (i16, i16, i16) {
-    if let Some(mate_in) = eval.mate_in() {
-        return match mate_in {
-            _ if mate_in > 0 => (1000, 0, 0),
-            _ if mate_in < 0 => (0, 0, 1000),
-            _ => unreachable!(),
+fn lmr_lookup() -> LmrLookup {
+    let base = tunables::lmr_base() as f32 / 100.0;
+    let div = tunables::lmr_div() as f32 / 100.0;
+    LookUp2d::new(|depth, mv| {
+        if depth == 0 || mv == 0 {
+            0
+        } else {
+            (base + (depth as f32).ln() * (mv as f32).ln() / div) as u32
+        }
+    })
+}
+
+fn lmp_lookup() -> LmpLookup {
+    let base = tunables::lmp_base() as f32 / 100.0;
+    let improving_div = tunables::lmp_improving_div() as f32 / 100.0;
+    LookUp2d::new(|depth, improving| {
+        let mut x = base + depth as f32 * depth as f32;
+        if improving == 0 {
+            x /= improving_div;
+        }
+        x as usize
+    })
+}
+
+fn aspiration_window() -> Window {
+    Window::new(
+        tunables::window_start(),
+        tunables::window_factor(),
+        100,
+        tunables::window_add(),
+    )
+}
+
+/// Evaluation as reported to the user, without the aggression added to the search score
+pub fn reported_eval(board: &Board, eval: Evaluation) -> Evaluation {
+    let scale = board.occupied().len() - board.pieces(Piece::Pawn).len();
+    remove_aggression(eval, scale as i32 * 2)
+}
+
+/// Converts a [reported](reported_eval) evaluation into win, loss and draw permille
+pub fn to_wld(eval: Evaluation) -> (i16, i16, i16) {
+    if eval.is_mate() {
+        return match eval.raw() > 0 {
+            true => (1000, 0, 0),
+            false => (0, 1000, 0),
         };
     }
     let raw = eval.raw().clamp(-2000, 2000) as f32 * 0.01;
@@ -220,6 +277,43 @@ fn to_wld(eval: Evaluation) -> (i16, i16, i16) {
     )
 }
 
+/// Number of legal moves at the root
+fn root_move_cnt(board: &Board) -> usize {
+    let mut move_cnt = 0;
+    board.generate_moves(|piece_moves| {
+        move_cnt += piece_moves.len();
+        false
+    });
+    move_cnt
+}
+
+/// PV of the last root search
+fn root_pv(thread: &ThreadContext) -> Vec<Move> {
+    let root_stack = &thread.ss[0];
+    root_stack.pv[..root_stack.pv_len]
+        .iter()
+        .map_while(|&make_move| make_move)
+        .collect()
+}
+
+/// Converts a root PV into UCI moves, truncated to at most `depth + 1` moves
+fn uci_pv(position: &mut Position, pv: &[Move], depth: u32, chess960: bool) -> Vec<Move> {
+    let mut uci_pv = vec![];
+    for &make_move in pv {
+        let mut uci_move = make_move;
+        uci::convert_move_to_uci(&mut uci_move, position.board(), chess960);
+        position.make_move(make_move);
+        uci_pv.push(uci_move);
+        if uci_pv.len() > depth as usize {
+            break;
+        }
+    }
+    for _ in 0..uci_pv.len() {
+        position.unmake_move()
+    }
+    uci_pv
+}
+
 pub struct AbRunner {
     shared_context: SharedContext,
     main_thread_context: Arc<Mutex<ThreadContext>>,
@@ -227,17 +321,22 @@ pub struct AbRunner {
     position: Position,
     chess960: bool,
     show_wdl: bool,
+    multi_pv: usize,
+    search_moves: Vec<Move>,
+    /// Principal variation of the last completed search
+    pv: Vec<Move>,
     thread_contexts: Vec<Arc<Mutex<ThreadContext>>>,
 }
 
 impl AbRunner {
-    fn launch_searcher<SM: 'static + SearchMode + Send, Info: 'static + GuiInfo + Send>(
+    fn launch_searcher<SM: 'static + SearchMode + Send>(
         &mut self,
         local_context: Arc<Mutex<ThreadContext>>,
         thread: usize,
         chess960: bool,
         show_wdl: bool,
-    ) -> impl FnMut() -> (Option<Move>, Evaluation, u32, u64) {
+        gui_info: Arc<dyn GuiInfo>,
+    ) -> impl FnMut() -> (Option<Move>, Evaluation, u32, u64, Vec<Move>) {
         let main_thread = thread == 0;
         let shared_context = self.shared_context.clone();
 
@@ -252,75 +351,150 @@ impl AbRunner {
         };
         let mut position = self.position.clone();
         let mut debugger = SM::new(self.position.board());
-        let gui_info = Info::new();
+        let mut root_move_cnt = root_move_cnt(self.position.board());
+        if !self.search_moves.is_empty() {
+            root_move_cnt = root_move_cnt.min(self.search_moves.len());
+        }
+        let multi_pv = self.multi_pv.min(root_move_cnt).max(1);
+        let search_moves = self.search_moves.clone();
         move || {
             let mut local_context = local_context.lock().unwrap();
 
             let mut nodes = 0;
             local_context.reset();
             local_context.stm = position.board().side_to_move();
+            local_context.search_moves.clone_from(&search_moves);
+            local_context.chess960 = chess960;
+            local_context.gui_info = match main_thread {
+                true => Some(gui_info.clone()),
+                false => None,
+            };
             let start_time = Instant::now();
+            let search_info = |position: &mut Position,
+                               multi_pv: usize,
+                               sel_depth: u32,
+                               depth: u32,
+                               eval: Evaluation,
+                               bounds: Bounds,
+                               pv: &[Move]| {
+                let eval = reported_eval(position.board(), eval);
+                let wld = match show_wdl {
+                    true => Some(to_wld(eval)),
+                    false => None,
+                };
+                SearchInfo {
+                    multi_pv,
+                    sel_depth,
+                    depth,
+                    eval,
+                    bounds,
+                    wld,
+                    elapsed: shared_context.elapsed(),
+                    nodes: node_counter.as_ref().unwrap().get_node_count(),
+                    hashfull: shared_context.get_t_table().hashfull(),
+                    pv: uci_pv(position, pv, depth, chess960),
+                }
+            };
             let mut best_move = None;
+            let mut best_pv = vec![];
             let mut eval: Option<Evaluation> = None;
+            let mut line_evals: Vec<Option<Evaluation>> = vec![None; multi_pv];
             let mut depth = 1_u32;
             let mut abort = false;
             'outer: loop {
-                let mut fail_cnt = 0;
-                local_context.window.reset();
-                loop {
-                    if abort {
-                        break 'outer;
+                let mut lines = vec![];
+                local_context.excluded_moves.clear();
+                for (pv_index, prev_line_eval) in line_evals.iter_mut().enumerate() {
+                    let line_eval = *prev_line_eval;
+                    let mut fail_cnt = 0;
+                    if let Some(line_eval) = line_eval {
+                        local_context.window.set(line_eval);
                     }
-                    let (alpha, beta) = if eval.is_some()
-                        && eval.unwrap().raw().abs() < 1000
-                        && depth > 4
-                        && fail_cnt < 10
-                    {
-                        local_context.window.get()
-                    } else {
-                        (Evaluation::min(), Evaluation::max())
-                    };
-                    local_context.sel_depth = 0;
-                    let score = search::search::<Pv>(
-                        &mut position,
-                        &mut local_context,
-                        &shared_context,
-                        0,
-                        depth,
-                        alpha,
-                        beta,
-                        false,
-                    );
-                    nodes = local_context.nodes();
-                    if depth > 1 && local_context.abort {
-                        break 'outer;
-                    }
-                    local_context.window.set(score);
-                    local_context.eval = score;
-
-                    let root_move = local_context.ss[0].pv[0].unwrap();
-                    shared_context.time_manager.deepen(
-                        thread,
-                        depth,
-                        local_context.root_nodes[root_move.from as usize][root_move.to as usize],
-                        nodes,
-                        local_context.eval,
-                        root_move,
-                    );
-                    if (score > alpha && score < beta) || score.is_mate() {
-                        abort = shared_context.abort_deepening(depth, nodes);
-                        best_move = local_context.ss[0].pv[0];
-                        eval = Some(score);
-                        break;
-                    } else {
-                        fail_cnt += 1;
-                        if score <= alpha {
-                            local_context.window.fail_low();
+                    local_context.window.reset();
+                    loop {
+                        if abort {
+                            break 'outer;
+                        }
+                        let (alpha, beta) = if line_eval.is_some()
+                            && line_eval.unwrap().raw().abs() < 1000
+                            && depth > 4
+                            && fail_cnt < 10
+                        {
+                            local_context.window.get()
+                        } else {
+                            (Evaluation::min(), Evaluation::max())
+                        };
+                        local_context.sel_depth = 0;
+                        let score = search::search::<Pv>(
+                            &mut position,
+                            &mut local_context,
+                            &shared_context,
+                            0,
+                            depth,
+                            alpha,
+                            beta,
+                            false,
+                        );
+                        nodes = local_context.nodes();
+                        if (depth > 1 || pv_index > 0) && local_context.abort {
+                            break 'outer;
+                        }
+                        local_context.window.set(score);
+
+                        let root_move = local_context.ss[0].pv[0].unwrap();
+                        if pv_index == 0 {
+                            local_context.eval = score;
+                            shared_context.time_manager.deepen(
+                                thread,
+                                depth,
+                                local_context.root_nodes[root_move.from as usize]
+                                    [root_move.to as usize],
+                                nodes,
+                                local_context.eval,
+                                root_move,
+                            );
+                        }
+                        if (score > alpha && score < beta) || score.is_mate() {
+                            let pv = root_pv(&local_context);
+                            if pv_index == 0 {
+                                best_move = Some(root_move);
+                                best_pv.clone_from(&pv);
+                                eval = Some(score);
+                            }
+                            *prev_line_eval = Some(score);
+                            lines.push((score, local_context.sel_depth, pv));
+                            local_context.excluded_moves.push(root_move);
+                            break;
                         } else {
-                            local_context.window.fail_high();
+                            fail_cnt += 1;
+                            let bounds = match score <= alpha {
+                                true => Bounds::UpperBound,
+                                false => Bounds::LowerBound,
+                            };
+                            match bounds {
+                                Bounds::UpperBound => local_context.window.fail_low(),
+                                _ => local_context.window.fail_high(),
+                            }
+                            if main_thread && multi_pv == 1 && start_time.elapsed() > REPORT_DELAY {
+                                let pv = match bounds {
+                                    Bounds::UpperBound => best_pv.clone(),
+                                    _ => root_pv(&local_context),
+                                };
+                                let info = search_info(
+                                    &mut position,
+                                    1,
+                                    local_context.sel_depth,
+                                    depth,
+                                    score,
+                                    bounds,
+                                    &pv,
+                                );
+                                gui_info.print_info(&info);
+                            }
                         }
                     }
                 }
+                abort = shared_context.abort_deepening(depth, nodes, eval);
                 if main_thread {
                     debugger.push(SearchStats::new(
                         start_time.elapsed().as_millis(),
@@ -329,51 +503,30 @@ impl AbRunner {
                         best_move,
                     ));
 
-                    let mut pv = vec![];
-                    let root_stack = &local_context.ss[0];
-                    for make_move in &root_stack.pv[..root_stack.pv_len] {
-                        if let Some(make_move) = *make_move {
-                            let mut uci_move = make_move;
-                            uci::convert_move_to_uci(&mut uci_move, position.board(), chess960);
-                            position.make_move(make_move);
-                            pv.push(uci_move);
-                            if pv.len() > depth as usize {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                    for _ in 0..pv.len() {
-                        position.unmake_move()
+                    for (pv_index, (line_eval, sel_depth, line_pv)) in lines.iter().enumerate() {
+                        let info = search_info(
+                            &mut position,
+                            pv_index + 1,
+                            *sel_depth,
+                            depth,
+                            *line_eval,
+                            Bounds::Exact,
+                            line_pv,
+                        );
+                        gui_info.print_info(&info);
                     }
-                    let total_nodes = node_counter.as_ref().unwrap().get_node_count();
-                    let scale = position.board().occupied().len()
-                        - position.board().pieces(Piece::Pawn).len();
-                    let eval = remove_aggression(eval.unwrap(), scale as i32 * 2);
-                    let wld = match show_wdl {
-                        true => Some(to_wld(eval)),
-                        false => None,
-                    };
-                    gui_info.print_info(
-                        local_context.sel_depth,
-                        depth,
-                        eval,
-                        wld,
-                        start_time.elapsed(),
-                        total_nodes,
-                        &pv,
-                    );
                 }
 
                 depth += 1;
-                if depth > 1 && shared_context.abort_deepening(depth, nodes) {
+                if depth > 1 && shared_context.abort_deepening(depth, nodes, eval) {
                     break 'outer;
                 }
             }
+            // Releases the reporter so its owner can tell the search ended
+            local_context.gui_info = None;
             if let Some(evaluation) = eval {
                 debugger.complete();
-                (best_move, evaluation, depth, nodes)
+                (best_move, evaluation, depth, nodes, best_pv)
             } else {
                 panic!("# Search function has failed to evaluate the position");
             }
@@ -389,24 +542,12 @@ impl AbRunner {
             shared_context: SharedContext {
                 time_manager,
                 t_table: Arc::new(TranspositionTable::new(16 * 1024 * 1024 / 12)),
-                lmr_lookup: Arc::new(LookUp2d::new(|depth, mv| {
-                    if depth == 0 || mv == 0 {
-                        0
-                    } else {
-                        (0.50 + (depth as f32).ln() * (mv as f32).ln() / 2.05) as u32
-                    }
-                })),
-                lmp_lookup: Arc::new(LookUp2d::new(|depth, improving| {
-                    let mut x = 2.97 + depth as f32 * depth as f32;
-                    if improving == 0 {
-                        x /= 1.94;
-                    }
-                    x as usize
-                })),
+                lmr_lookup: Arc::new(lmr_lookup()),
+                lmp_lookup: Arc::new(lmp_lookup()),
                 start: Instant::now(),
             },
             main_thread_context: Arc::new(Mutex::new(ThreadContext {
-                window: Window::new(15, 45, 100, 9),
+                window: aspiration_window(),
                 tt_hits: 0,
                 tt_misses: 0,
                 eval: position.get_eval(),
@@ -428,16 +569,25 @@ impl AbRunner {
                 abort: false,
                 stm: Color::White,
                 root_nodes: [[0; Square::NUM]; Square::NUM],
+                excluded_moves: vec![],
+                search_moves: vec![],
+                chess960: false,
+                gui_info: None,
             })),
             thread_contexts: vec![],
             position,
             chess960: false,
             show_wdl: false,
+            multi_pv: 1,
+            search_moves: vec![],
+            pv: vec![],
         }
     }
 
-    pub fn search<SM: 'static + SearchMode + Send, Info: 'static + GuiInfo + Send>(
+    /// Searches the current position, progress of the main thread is reported to `gui_info`
+    pub fn search<SM: 'static + SearchMode + Send>(
         &mut self,
+        gui_info: Arc<dyn GuiInfo>,
     ) -> (Move, Evaluation, u32, u64) {
         let thread_count = self.thread_contexts.len() as u8 + 1;
         let mut join_handlers = vec![];
@@ -446,27 +596,31 @@ impl AbRunner {
             .initialize_node_counters(thread_count as usize);
         self.position.reset();
         for (i, context) in self.thread_contexts.clone().iter().enumerate() {
-            join_handlers.push(std::thread::spawn(self.launch_searcher::<SM, NoInfo>(
+            join_handlers.push(std::thread::spawn(self.launch_searcher::<SM>(
                 context.clone(),
                 i + 1,
                 self.chess960,
                 self.show_wdl,
+                Arc::new(NoInfo),
             )));
         }
 
-        let (final_move, final_eval, max_depth, mut node_count) = self.launch_searcher::<SM, Info>(
-            self.main_thread_context.clone(),
-            0,
-            self.chess960,
-            self.show_wdl,
-        )();
+        let (final_move, final_eval, max_depth, mut node_count, final_pv) = self
+            .launch_searcher::<SM>(
+                self.main_thread_context.clone(),
+                0,
+                self.chess960,
+                self.show_wdl,
+                gui_info,
+            )();
         for join_handler in join_handlers {
-            let (_, _, _, nodes) = join_handler.join().unwrap();
+            let (_, _, _, nodes, _) = join_handler.join().unwrap();
             node_count += nodes;
         }
         if final_move.is_none() {
             panic!("# All move generation has failed");
         }
+        self.pv = final_pv;
         self.shared_context.t_table.age();
         (final_move.unwrap(), final_eval, max_depth, node_count)
     }
@@ -491,16 +645,59 @@ impl AbRunner {
         self.shared_context.t_table.clean();
     }
 
+    pub fn save_hash(&self, path: &str) -> std::io::Result<()> {
+        self.shared_context.t_table.save(path)
+    }
+
+    /// Replaces the transposition table with one saved by [save_hash](Self::save_hash)
+    pub fn load_hash(&mut self, path: &str) -> std::io::Result<()> {
+        self.shared_context.t_table = Arc::new(TranspositionTable::load(path)?);
+        Ok(())
+    }
+
     pub fn set_board(&mut self, board: Board) {
         self.position.set_board(board);
+        self.search_moves.clear();
+        self.pv.clear();
     }
 
     pub fn make_move(&mut self, make_move: Move) {
         self.position.make_move(make_move);
         self.position.reset();
+        self.search_moves.clear();
+        self.pv.clear();
+    }
+
+    /// Returns the expected reply to the best move of the last search
+    /// - Falls back to the transposition table if the PV is too short
+    pub fn ponder_move(&self) -> Option<Move> {
+        let &best_move = self.pv.first()?;
+        if let Some(&ponder_move) = self.pv.get(1) {
+            return Some(ponder_move);
+        }
+        let mut board = self.position.board().clone();
+        board.play_unchecked(best_move);
+        let entry = self.shared_context.t_table.get(&board)?;
+        entry.table_move.filter(|&mv| board.is_legal(mv))
+    }
+
+    /// Principal variation of the last completed search
+    pub fn pv(&self) -> &[Move] {
+        &self.pv
+    }
+
+    /// Restricts the next search to the given legal root moves
+    /// - An empty list searches all root moves
+    /// - Duplicates are removed, the MultiPV count is limited by the distinct moves
+    pub fn set_search_moves(&mut self, search_moves: Vec<Move>) {
+        self.search_moves.clear();
+        for make_move in search_moves {
+            if !self.search_moves.contains(&make_move) {
+                self.search_moves.push(make_move);
+            }
+        }
     }
 
-    #[cfg(feature = "data")]
     pub fn get_position(&self) -> &Position {
         &self.position
     }
@@ -509,6 +706,10 @@ impl AbRunner {
         self.position.board()
     }
 
+    pub fn set_network(&mut self, nnue: Nnue) {
+        self.position.set_evaluator(nnue);
+    }
+
     pub fn set_chess960(&mut self, chess960: bool) {
         self.chess960 = chess960;
     }
@@ -516,4 +717,18 @@ impl AbRunner {
     pub fn set_uci_show_wdl(&mut self, show_wdl: bool) {
         self.show_wdl = show_wdl;
     }
+
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.multi_pv = multi_pv;
+    }
+
+    /// Rebuilds the lookup tables and aspiration windows after [tunables] changed
+    #[cfg(feature = "tune")]
+    pub fn reload_params(&mut self) {
+        self.shared_context.lmr_lookup = Arc::new(lmr_lookup());
+        self.shared_context.lmp_lookup = Arc::new(lmp_lookup());
+        for context in std::iter::once(&self.main_thread_context).chain(&self.thread_contexts) {
+            context.lock().unwrap().window = aspiration_window();
+        }
+    }
 }
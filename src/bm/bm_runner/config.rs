@@ -1,4 +1,5 @@
 use crate::bm::bm_util::eval::Evaluation;
+use crate::bm::bm_util::t_table::Bounds;
 use cozy_chess::{Board, Move};
 use std::fmt::{Display, Write as FmtWrite};
 use std::fs::OpenOptions;
@@ -125,84 +126,116 @@ fn extend(string: &mut String, len: usize) {
     }
 }
 
-pub trait GuiInfo {
-    fn new() -> Self;
+/// Search progress of a single PV line
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    /// 1 based index of the PV line
+    pub multi_pv: usize,
+    pub sel_depth: u32,
+    pub depth: u32,
+    pub eval: Evaluation,
+    /// [Exact](Bounds::Exact) unless the aspiration window failed high or low
+    pub bounds: Bounds,
+    pub wld: Option<(i16, i16, i16)>,
+    pub elapsed: Duration,
+    pub nodes: u64,
+    /// Permille of the transposition table written to in the current search
+    pub hashfull: u32,
+    pub pv: Vec<Move>,
+}
 
-    fn print_info(
-        &self,
-        sel_depth: u32,
-        depth: u32,
-        eval: Evaluation,
-        wld: Option<(i16, i16, i16)>,
-        elapsed: Duration,
-        node_cnt: u64,
-        pv: &[Move],
-    );
+/// Receives search progress, only called from the main search thread
+pub trait GuiInfo: Send + Sync {
+    fn print_info(&self, info: &SearchInfo);
+
+    /// Reports the root move currently being searched, `move_number` is 1 based
+    fn print_cur_move(&self, depth: u32, make_move: Move, move_number: usize);
 }
 
 #[derive(Debug, Clone)]
 pub struct NoInfo;
 
 impl GuiInfo for NoInfo {
-    fn new() -> Self {
-        Self {}
-    }
+    fn print_info(&self, _: &SearchInfo) {}
 
-    fn print_info(
-        &self,
-        _: u32,
-        _: u32,
-        _: Evaluation,
-        _: Option<(i16, i16, i16)>,
-        _: Duration,
-        _: u64,
-        _: &[Move],
-    ) {
-    }
+    fn print_cur_move(&self, _: u32, _: Move, _: usize) {}
 }
 
 #[derive(Debug, Clone)]
 pub struct UciInfo;
 
 impl GuiInfo for UciInfo {
-    fn new() -> Self {
-        Self {}
-    }
-
-    fn print_info(
-        &self,
-        seldepth: u32,
-        depth: u32,
-        eval: Evaluation,
-        wld: Option<(i16, i16, i16)>,
-        elapsed: Duration,
-        node_cnt: u64,
-        pv: &[Move],
-    ) {
+    fn print_info(&self, info: &SearchInfo) {
+        let eval = info.eval;
         let eval_str = if eval.is_mate() {
             format!("mate {}", eval.mate_in().unwrap())
         } else {
             format!("cp {}", eval.raw())
         };
-        let nps = (node_cnt as u128 * 1000) / elapsed.as_millis().max(1);
+        let bounds = match info.bounds {
+            Bounds::LowerBound => "lowerbound ",
+            Bounds::Exact => "",
+            Bounds::UpperBound => "upperbound ",
+        };
+        let nps = (info.nodes as u128 * 1000) / info.elapsed.as_millis().max(1);
 
-        let wdl = match wld {
+        let wdl = match info.wld {
             Some(wld) => format!("wdl {} {} {} ", wld.0, wld.2, wld.1),
             None => "".to_string(),
         };
         let mut output = format!(
-            "info depth {} seldepth {} score {} {}time {} nodes {} nps {} pv",
-            depth,
-            seldepth,
+            "info depth {} seldepth {} multipv {} score {} {}{}time {} nodes {} nps {} hashfull {} pv",
+            info.depth,
+            info.sel_depth,
+            info.multi_pv,
             eval_str,
+            bounds,
             wdl,
-            elapsed.as_millis(),
-            node_cnt,
-            nps
+            info.elapsed.as_millis(),
+            info.nodes,
+            nps,
+            info.hashfull,
         );
-        for make_move in pv {
+        for make_move in &info.pv {
             write!(&mut output, " {}", make_move).unwrap();
         }
         println!("{}", output);
     }
+
+    fn print_cur_move(&self, depth: u32, make_move: Move, move_number: usize) {
+        println!(
+            "info depth {} currmove {} currmovenumber {}",
+            depth, make_move, move_number
+        );
+    }
+}
+
+/// Thinking output in the xboard `ply score time nodes pv` format
+#[derive(Debug, Clone)]
+pub struct XBoardInfo;
+
+impl GuiInfo for XBoardInfo {
+    fn print_info(&self, info: &SearchInfo) {
+        if info.multi_pv != 1 || info.bounds != Bounds::Exact {
+            return;
+        }
+        let score = match info.eval.mate_in() {
+            Some(mate_in) if mate_in < 0 => -100000 + mate_in as i32,
+            Some(mate_in) => 100000 + mate_in as i32,
+            None => info.eval.raw() as i32,
+        };
+        let mut output = format!(
+            "{} {} {} {}",
+            info.depth,
+            score,
+            info.elapsed.as_millis() / 10,
+            info.nodes
+        );
+        for make_move in &info.pv {
+            write!(&mut output, " {}", make_move).unwrap();
+        }
+        println!("{}", output);
+    }
+
+    fn print_cur_move(&self, _: u32, _: Move, _: usize) {}
 }
@@ -1,8 +1,9 @@
 use crate::bm::bm_util::eval::Evaluation;
+use crate::bm::bm_util::tunables;
 use cozy_chess::{Board, Move};
 use std::fmt::Debug;
 use std::sync::atomic::{AtomicBool, AtomicI16, AtomicU32, AtomicU64, Ordering};
-use std::sync::Mutex;
+use std::sync::{Condvar, Mutex};
 use std::time::{Duration, Instant};
 
 use super::ab_runner::MAX_PLY;
@@ -18,6 +19,13 @@ const NODES_DEFAULT: u64 = u64::MAX;
 
 const MOVES_TO_GO_DEFAULT: Option<u32> = None;
 
+const MATE_DEFAULT: u32 = 0;
+
+pub const MOVE_OVERHEAD_DEFAULT: Duration = Duration::from_millis(10);
+
+/// Minimum fraction of the remaining time that can be used regardless of move overhead
+const MIN_TIME_DIVISOR: u32 = 10;
+
 #[derive(Debug, Copy, Clone)]
 pub enum TimeManagementInfo {
     WTime(Duration),
@@ -28,6 +36,10 @@ pub enum TimeManagementInfo {
     MaxNodes(u64),
     MovesToGo(u32),
     MoveTime(Duration),
+    /// Search for a mate in at most the given amount of moves
+    Mate(u32),
+    Ponder,
+    Infinite,
     Unknown,
 }
 
@@ -45,11 +57,23 @@ pub struct TimeManager {
     board: Mutex<Board>,
 
     infinite: AtomicBool,
+    /// Search was started with `go infinite`, the result is held until stopped
+    go_infinite: AtomicBool,
+    /// Clock isn't running until [ponder_hit](Self::ponder_hit) is called
+    ponder: AtomicBool,
+    /// Instant the clock started running at if the search started as ponder search
+    ponder_hit: Mutex<Option<Instant>>,
     abort_now: AtomicBool,
+    /// Notified by [abort_now](Self::abort_now) and [ponder_hit](Self::ponder_hit)
+    stop_signal: (Mutex<()>, Condvar),
     no_manage: AtomicBool,
 
     max_depth: AtomicU32,
     max_nodes: AtomicU64,
+    /// Time in milliseconds reserved for communication latency
+    move_overhead: AtomicU32,
+    /// Stop deepening once a mate in this many moves is found, 0 if disabled
+    mate: AtomicU32,
 }
 
 impl TimeManager {
@@ -64,10 +88,16 @@ impl TimeManager {
             prev_move: Mutex::new(None),
             board: Mutex::new(Board::default()),
             abort_now: AtomicBool::new(false),
+            stop_signal: (Mutex::new(()), Condvar::new()),
             infinite: AtomicBool::new(true),
+            go_infinite: AtomicBool::new(false),
+            ponder: AtomicBool::new(false),
+            ponder_hit: Mutex::new(None),
             no_manage: AtomicBool::new(true),
             max_depth: AtomicU32::new(DEPTH_DEFAULT),
             max_nodes: AtomicU64::new(NODES_DEFAULT),
+            move_overhead: AtomicU32::new(MOVE_OVERHEAD_DEFAULT.as_millis() as u32),
+            mate: AtomicU32::new(MATE_DEFAULT),
         }
     }
 }
@@ -97,9 +127,14 @@ impl TimeManager {
         };
         *prev_move = Some(mv);
         self.move_stability.store(move_stability, Ordering::Relaxed);
-        let move_stability_factor = (41 - move_stability) as f32 * 0.024;
-        let node_factor = (1.0 - move_nodes as f32 / nodes as f32) * 3.42 + 0.52;
-        let eval_factor = (prev_eval - eval).clamp(18, 20) as f32 * 0.088;
+        let move_stability_factor = (tunables::tm_stability_base() - move_stability) as f32
+            * tunables::tm_stability_scale() as f32
+            * 0.001;
+        let node_factor =
+            (1.0 - move_nodes as f32 / nodes as f32) * tunables::tm_node_scale() as f32 * 0.01
+                + tunables::tm_node_base() as f32 * 0.01;
+        let eval_factor =
+            (prev_eval - eval).clamp(18, 20) as f32 * tunables::tm_eval_scale() as f32 * 0.001;
         let base_duration = self.base_duration.load(Ordering::Relaxed);
         let target_duration =
             base_duration as f32 * move_stability_factor * node_factor * eval_factor;
@@ -118,6 +153,8 @@ impl TimeManager {
         });
 
         let mut infinite = true;
+        let mut ponder = false;
+        let mut go_infinite = false;
 
         let mut w_time = TIME_DEFAULT;
         let mut b_time = TIME_DEFAULT;
@@ -127,6 +164,7 @@ impl TimeManager {
         let mut max_nodes = NODES_DEFAULT;
         let mut moves_to_go = MOVES_TO_GO_DEFAULT;
         let mut move_time = None;
+        let mut mate = MATE_DEFAULT;
 
         for info in info {
             match info {
@@ -157,12 +195,25 @@ impl TimeManager {
                     move_time = Some(*time);
                     infinite = false;
                 }
+                TimeManagementInfo::Mate(moves) => {
+                    mate = *moves;
+                }
+                TimeManagementInfo::Ponder => {
+                    ponder = true;
+                }
+                TimeManagementInfo::Infinite => {
+                    go_infinite = true;
+                }
                 _ => {}
             }
         }
         self.infinite.store(infinite, Ordering::SeqCst);
+        self.ponder.store(ponder, Ordering::SeqCst);
+        self.go_infinite.store(go_infinite, Ordering::SeqCst);
+        *self.ponder_hit.lock().unwrap() = None;
         self.max_depth.store(max_depth, Ordering::SeqCst);
         self.max_nodes.store(max_nodes, Ordering::SeqCst);
+        self.mate.store(mate, Ordering::SeqCst);
 
         let (time, inc) = match board.side_to_move() {
             cozy_chess::Color::White => (w_time, w_inc),
@@ -173,16 +224,17 @@ impl TimeManager {
         self.no_manage.store(no_manage, Ordering::SeqCst);
 
         if let Some(move_time) = move_time {
-            let move_time = move_time.as_millis() as u32;
+            let move_time = self.lag_adjusted(move_time);
             self.target_duration.store(move_time, Ordering::SeqCst);
             self.max_duration.store(move_time, Ordering::SeqCst);
         } else if move_cnt == 0 {
             self.target_duration.store(0, Ordering::SeqCst);
         } else {
-            let max_time = time.as_millis() as u32 * 4 / 5;
+            let time = self.lag_adjusted(time);
+            let max_time = time * 4 / 5;
             let expected_moves = moves_to_go.unwrap_or(EXPECTED_MOVES) + 1;
             let default = if move_cnt > 1 {
-                (inc.as_millis() as u32 + time.as_millis() as u32 / expected_moves).min(max_time)
+                (inc.as_millis() as u32 + time / expected_moves).min(max_time)
             } else {
                 0
             };
@@ -192,30 +244,96 @@ impl TimeManager {
         };
     }
 
+    pub fn set_move_overhead(&self, move_overhead: Duration) {
+        self.move_overhead
+            .store(move_overhead.as_millis() as u32, Ordering::SeqCst);
+    }
+
+    /// Milliseconds available after subtracting move overhead
+    /// - Never goes below a fraction of the given time so very low clocks still get a search
+    fn lag_adjusted(&self, time: Duration) -> u32 {
+        let time = time.as_millis() as u32;
+        let move_overhead = self.move_overhead.load(Ordering::SeqCst);
+        time.saturating_sub(move_overhead)
+            .max(time / MIN_TIME_DIVISOR)
+    }
+
     pub fn abort_now(&self) {
         self.abort_now.store(true, Ordering::SeqCst);
+        self.notify_stop();
+    }
+
+    /// Switches a ponder search to a normal search, the clock starts running from this point
+    pub fn ponder_hit(&self) {
+        *self.ponder_hit.lock().unwrap() = Some(Instant::now());
+        self.ponder.store(false, Ordering::SeqCst);
+        self.notify_stop();
+    }
+
+    /// Wakes [wait_for_stop](Self::wait_for_stop), the lock is taken so a waiter
+    /// can't miss a change made between its check and going to sleep
+    fn notify_stop(&self) {
+        let (lock, signal) = &self.stop_signal;
+        let _guard = lock.lock().unwrap();
+        signal.notify_all();
+    }
+
+    /// Blocks until the search result may be reported
+    /// - Infinite searches wait for [abort_now](Self::abort_now)
+    /// - Ponder searches wait for [abort_now](Self::abort_now) or [ponder_hit](Self::ponder_hit)
+    pub fn wait_for_stop(&self) {
+        let (lock, signal) = &self.stop_signal;
+        let mut guard = lock.lock().unwrap();
+        while !self.abort_now.load(Ordering::SeqCst)
+            && (self.go_infinite.load(Ordering::SeqCst) || self.ponder.load(Ordering::SeqCst))
+        {
+            guard = signal.wait(guard).unwrap();
+        }
+    }
+
+    /// Returns true if the clock isn't running
+    fn untimed(&self) -> bool {
+        self.infinite.load(Ordering::SeqCst) || self.ponder.load(Ordering::SeqCst)
+    }
+
+    /// Milliseconds the clock has been running for
+    fn elapsed(&self, start: Instant) -> u32 {
+        let start = self.ponder_hit.lock().unwrap().unwrap_or(start);
+        start.elapsed().as_millis() as u32
     }
 
     pub fn abort_search(&self, start: Instant, nodes: u64) -> bool {
         if self.abort_now.load(Ordering::SeqCst) {
             true
         } else {
-            (self.max_duration.load(Ordering::SeqCst) < start.elapsed().as_millis() as u32
-                && !self.infinite.load(Ordering::SeqCst))
+            (!self.untimed() && self.max_duration.load(Ordering::SeqCst) < self.elapsed(start))
                 || self.max_nodes.load(Ordering::SeqCst) <= nodes
         }
     }
 
-    pub fn abort_deepening(&self, start: Instant, depth: u32, nodes: u64) -> bool {
+    /// Returns true if the root score proves a mate short enough for `go mate`
+    fn mate_found(&self, eval: Option<Evaluation>) -> bool {
+        let mate = self.mate.load(Ordering::SeqCst);
+        let mate_in = eval.and_then(|eval| eval.mate_in());
+        mate != 0 && mate_in.is_some_and(|mate_in| mate_in > 0 && mate_in as u32 <= mate)
+    }
+
+    pub fn abort_deepening(
+        &self,
+        start: Instant,
+        depth: u32,
+        nodes: u64,
+        eval: Option<Evaluation>,
+    ) -> bool {
         if self.abort_now.load(Ordering::SeqCst) {
             true
         } else {
-            let abort_std = self.target_duration.load(Ordering::SeqCst)
-                < start.elapsed().as_millis() as u32
-                && !self.infinite.load(Ordering::SeqCst);
+            let abort_std = !self.untimed()
+                && self.target_duration.load(Ordering::SeqCst) < self.elapsed(start);
             abort_std
                 || self.max_depth.load(Ordering::SeqCst) < depth
                 || self.max_nodes.load(Ordering::SeqCst) <= nodes
+                || self.mate_found(eval)
         }
     }
 
@@ -1,4 +1,5 @@
 pub mod move_entry;
 pub mod move_gen;
+pub mod move_gen_check;
 pub mod search;
 mod see;
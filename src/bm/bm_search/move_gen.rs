@@ -49,6 +49,8 @@ pub struct OrderedMoveGen {
     quiets: ArrayVec<ScoredMove, MAX_MOVES>,
     captures: ArrayVec<ScoredMove, MAX_MOVES>,
     bad_captures: ArrayVec<ScoredMove, MAX_MOVES>,
+
+    search_moves: Vec<Move>,
 }
 
 fn select_highest(array: &[ScoredMove]) -> Option<usize> {
@@ -80,9 +82,16 @@ impl OrderedMoveGen {
             quiets: ArrayVec::new(),
             captures: ArrayVec::new(),
             bad_captures: ArrayVec::new(),
+            search_moves: vec![],
         }
     }
 
+    /// Restricts generation to the given moves, used for `go searchmoves` at root
+    /// - An empty list allows all moves
+    pub fn set_search_moves(&mut self, search_moves: &[Move]) {
+        self.search_moves = search_moves.to_vec();
+    }
+
     /// Returns what phase of move generation the last generated came from
     /// with the exception of TT move.
     ///
@@ -111,6 +120,20 @@ impl OrderedMoveGen {
         pos: &Position,
         hist: &History,
         hist_indices: &HistoryIndices,
+    ) -> Option<Move> {
+        loop {
+            let mv = self.next_unfiltered(pos, hist, hist_indices)?;
+            if self.search_moves.is_empty() || self.search_moves.contains(&mv) {
+                return Some(mv);
+            }
+        }
+    }
+
+    fn next_unfiltered(
+        &mut self,
+        pos: &Position,
+        hist: &History,
+        hist_indices: &HistoryIndices,
     ) -> Option<Move> {
         if self.phase == Phase::PvMove {
             self.phase = Phase::GenPieceMoves;
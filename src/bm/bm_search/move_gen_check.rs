@@ -0,0 +1,260 @@
+use std::fmt::Display;
+
+use cozy_chess::{Board, Move, Piece, Square};
+
+use super::move_entry::MoveEntry;
+use super::move_gen::{OrderedMoveGen, QSearchMoveGen};
+use crate::bm::bm_runner::ab_runner::MoveData;
+use crate::bm::bm_util::history::{History, HistoryIndices};
+use crate::bm::bm_util::position::Position;
+use crate::bm::bm_util::xor_shift::XorShift;
+
+#[derive(Debug, Clone)]
+pub enum MoveGenError {
+    /// Generated move isn't legal or isn't allowed by the generator
+    Illegal(Move),
+    /// Generated legal move that isn't one of the search moves
+    OutsideSearchMoves(Move),
+    /// Generated move was already given
+    Duplicate(Move),
+    /// Legal move the generator was required to give but didn't
+    Missing(Move),
+}
+
+impl Display for MoveGenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveGenError::Illegal(make_move) => write!(f, "illegal move {}", make_move),
+            MoveGenError::OutsideSearchMoves(make_move) => {
+                write!(f, "move {} outside search moves", make_move)
+            }
+            MoveGenError::Duplicate(make_move) => write!(f, "duplicate move {}", make_move),
+            MoveGenError::Missing(make_move) => write!(f, "missing move {}", make_move),
+        }
+    }
+}
+
+/// Move between random squares, usually illegal
+fn any_move(rng: &mut XorShift) -> Move {
+    Move {
+        from: Square::index(rng.below(Square::NUM)),
+        to: Square::index(rng.below(Square::NUM)),
+        promotion: None,
+    }
+}
+
+fn move_data(rng: &mut XorShift) -> Option<MoveData> {
+    if rng.chance(4) {
+        return None;
+    }
+    let make_move = any_move(rng);
+    Some(MoveData {
+        from: make_move.from,
+        to: make_move.to,
+        promotion: None,
+        piece: Piece::index(rng.below(Piece::NUM)),
+        capture: false,
+    })
+}
+
+fn legal_moves(board: &Board) -> Vec<Move> {
+    let mut moves = vec![];
+    board.generate_moves(|piece_moves| {
+        moves.extend(piece_moves);
+        false
+    });
+    moves
+}
+
+fn is_capture(board: &Board, make_move: Move) -> bool {
+    board.colors(!board.side_to_move()).has(make_move.to)
+}
+
+/// Checks that every generated move is legal and given once and that
+/// no move the generator is required to give is missing
+fn check_generated(
+    board: &Board,
+    generated: &[Move],
+    required: impl Iterator<Item = Move>,
+) -> Result<(), MoveGenError> {
+    for (index, &make_move) in generated.iter().enumerate() {
+        if !board.is_legal(make_move) {
+            return Err(MoveGenError::Illegal(make_move));
+        }
+        if generated[..index].contains(&make_move) {
+            return Err(MoveGenError::Duplicate(make_move));
+        }
+    }
+    for make_move in required {
+        if !generated.contains(&make_move) {
+            return Err(MoveGenError::Missing(make_move));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that only search moves were generated if any are set
+fn check_search_moves(generated: &[Move], search_moves: &[Move]) -> Result<(), MoveGenError> {
+    match generated
+        .iter()
+        .find(|&make_move| !search_moves.is_empty() && !search_moves.contains(make_move))
+    {
+        Some(&make_move) => Err(MoveGenError::OutsideSearchMoves(make_move)),
+        None => Ok(()),
+    }
+}
+
+/// Runs [OrderedMoveGen] with random PV move, killers, history, search moves
+/// and [skip_quiets](OrderedMoveGen::skip_quiets) calls
+///
+/// All legal moves are required unless quiets are skipped, then only captures are required
+pub fn check_ordered(pos: &Position, rng: &mut XorShift) -> Result<(), MoveGenError> {
+    let board = pos.board();
+    let legal = legal_moves(board);
+
+    let pv_move = match rng.chance(2) {
+        true => rng.pick(&legal),
+        false => None,
+    };
+    let mut killers = MoveEntry::new();
+    for _ in 0..rng.below(4) {
+        let killer = match rng.chance(2) {
+            true => rng.pick(&legal).unwrap_or_else(|| any_move(rng)),
+            false => any_move(rng),
+        };
+        killers.push(killer);
+    }
+    let hist_indices = HistoryIndices::new(move_data(rng), move_data(rng), move_data(rng));
+    let mut hist = History::new();
+    for _ in 0..rng.below(16) {
+        let Some(cutoff_move) = rng.pick(&legal) else {
+            break;
+        };
+        let (captures, quiets): (Vec<Move>, Vec<Move>) = (0..rng.below(8))
+            .filter_map(|_| rng.pick(&legal))
+            .filter(|&make_move| make_move != cutoff_move)
+            .partition(|&make_move| is_capture(board, make_move));
+        let amt = rng.below(64) as i16;
+        hist.update_history(pos, &hist_indices, cutoff_move, &quiets, &captures, amt);
+    }
+    let search_moves = match rng.chance(4) {
+        true => legal
+            .iter()
+            .copied()
+            .filter(|_| rng.chance(2))
+            .collect::<Vec<_>>(),
+        false => vec![],
+    };
+
+    let mut move_gen = OrderedMoveGen::new(pv_move, killers);
+    move_gen.set_search_moves(&search_moves);
+    let skip_at = match rng.chance(2) {
+        true => Some(rng.below(legal.len() + 1)),
+        false => None,
+    };
+    let mut skipped = false;
+    let mut generated = vec![];
+    while let Some(make_move) = move_gen.next(pos, &hist, &hist_indices) {
+        generated.push(make_move);
+        if skip_at == Some(generated.len()) && !is_capture(board, make_move) {
+            move_gen.skip_quiets();
+            skipped = true;
+        }
+        if generated.len() > legal.len() {
+            break;
+        }
+    }
+    let required = legal.iter().copied().filter(|&make_move| {
+        (search_moves.is_empty() || search_moves.contains(&make_move))
+            && (!skipped || is_capture(board, make_move))
+    });
+    check_generated(board, &generated, required)?;
+    check_search_moves(&generated, &search_moves)
+}
+
+/// Runs [QSearchMoveGen] with random capture history, every capture
+/// except en-passant is required
+pub fn check_q_search(pos: &Position, rng: &mut XorShift) -> Result<(), MoveGenError> {
+    let board = pos.board();
+    let legal = legal_moves(board);
+    let captures = legal
+        .iter()
+        .copied()
+        .filter(|&make_move| is_capture(board, make_move))
+        .collect::<Vec<_>>();
+
+    let hist_indices = HistoryIndices::new(None, None, None);
+    let mut hist = History::new();
+    for _ in 0..rng.below(16) {
+        let Some(cutoff_move) = rng.pick(&captures) else {
+            break;
+        };
+        let failed = (0..rng.below(8))
+            .filter_map(|_| rng.pick(&captures))
+            .filter(|&make_move| make_move != cutoff_move)
+            .collect::<Vec<_>>();
+        let amt = rng.below(64) as i16;
+        hist.update_history(pos, &hist_indices, cutoff_move, &[], &failed, amt);
+    }
+
+    let mut move_gen = QSearchMoveGen::new();
+    let mut generated = vec![];
+    while let Some(make_move) = move_gen.next(pos, &hist) {
+        if !is_capture(board, make_move) {
+            return Err(MoveGenError::Illegal(make_move));
+        }
+        generated.push(make_move);
+        if generated.len() > captures.len() {
+            break;
+        }
+    }
+    check_generated(board, &generated, captures.into_iter())
+}
+
+/// Runs both checks on every position and every position reached by a random legal move
+/// - Returns the FEN of each failing position together with the error
+pub fn check_positions(
+    boards: impl Iterator<Item = Board>,
+    iterations: usize,
+    seed: u64,
+) -> Vec<(String, MoveGenError)> {
+    let mut rng = XorShift::new(seed);
+    let mut errors = vec![];
+    let mut pos = Position::new(Board::default());
+    for board in boards {
+        let mut checked = vec![board.clone()];
+        checked.extend(legal_moves(&board).into_iter().map(|make_move| {
+            let mut child = board.clone();
+            child.play_unchecked(make_move);
+            child
+        }));
+        for board in checked {
+            pos.set_board(board);
+            for _ in 0..iterations {
+                let result = check_ordered(&pos, &mut rng).and(check_q_search(&pos, &mut rng));
+                if let Err(err) = result {
+                    errors.push((format!("{}", pos.board()), err));
+                    break;
+                }
+            }
+        }
+    }
+    errors
+}
+
+#[test]
+fn move_gen_completeness() {
+    let errors = check_positions(crate::bm::uci::bench::bench_positions(), 4, 0x5eed);
+    for (fen, err) in &errors {
+        println!("{}: {}", fen, err);
+    }
+    assert!(errors.is_empty());
+
+    let [e4, d4] = ["e2e4", "d2d4"].map(|make_move| make_move.parse().unwrap());
+    assert!(check_search_moves(&[e4, d4], &[]).is_ok());
+    assert!(check_search_moves(&[d4], &[e4, d4]).is_ok());
+    assert!(matches!(
+        check_search_moves(&[e4, d4], &[e4]),
+        Err(MoveGenError::OutsideSearchMoves(make_move)) if make_move == d4
+    ));
+}
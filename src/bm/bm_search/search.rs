@@ -1,12 +1,16 @@
 use arrayvec::ArrayVec;
 use cozy_chess::{Board, Move, Piece};
 
-use crate::bm::bm_runner::ab_runner::{MoveData, SharedContext, ThreadContext, MAX_PLY};
+use crate::bm::bm_runner::ab_runner::{
+    MoveData, SharedContext, ThreadContext, MAX_PLY, REPORT_DELAY,
+};
 use crate::bm::bm_util::eval::Depth::Next;
 use crate::bm::bm_util::eval::Evaluation;
 use crate::bm::bm_util::history::HistoryIndices;
 use crate::bm::bm_util::position::Position;
 use crate::bm::bm_util::t_table::Bounds;
+use crate::bm::bm_util::tunables;
+use crate::bm::uci;
 
 use super::move_gen::{OrderedMoveGen, Phase, QSearchMoveGen};
 use super::see::compare_see;
@@ -43,20 +47,20 @@ const fn do_rev_fp(depth: u32) -> bool {
     depth <= 9
 }
 
-const fn rev_fp(depth: u32, improving: bool) -> i16 {
-    depth as i16 * 71 - improving as i16 * 62
+fn rev_fp(depth: u32, improving: bool) -> i16 {
+    depth as i16 * tunables::rev_fp_margin() - improving as i16 * tunables::rev_fp_improving()
 }
 
 const fn do_razor(depth: u32) -> bool {
     depth <= 4
 }
 
-const fn razor_margin(depth: u32) -> i16 {
-    depth as i16 * 306
+fn razor_margin(depth: u32) -> i16 {
+    depth as i16 * tunables::razor_margin()
 }
 
-const fn razor_qsearch() -> i16 {
-    277
+fn razor_qsearch() -> i16 {
+    tunables::razor_qsearch()
 }
 
 fn do_nmp<Search: SearchType>(
@@ -75,7 +79,9 @@ fn do_nmp<Search: SearchType>(
 
 fn nmp_depth(depth: u32, eval: i16, beta: i16) -> u32 {
     assert!(eval >= beta);
-    let r = 4 + depth * 23 / 60 + ((eval - beta) / 204) as u32;
+    let r = tunables::nmp_base()
+        + depth * tunables::nmp_depth_factor() / 60
+        + ((eval - beta) / tunables::nmp_eval_div()) as u32;
     depth.saturating_sub(r).max(1)
 }
 
@@ -87,20 +93,20 @@ const fn iir(depth: u32) -> u32 {
     }
 }
 
-const fn fp(depth: u32) -> i16 {
-    depth as i16 * 86
+fn fp(depth: u32) -> i16 {
+    depth as i16 * tunables::fp_margin()
 }
 
-const fn see_fp(depth: u32) -> i16 {
-    depth as i16 * 123
+fn see_fp(depth: u32) -> i16 {
+    depth as i16 * tunables::see_fp_margin()
 }
 
-const fn hp(depth: u32) -> i32 {
-    -((depth * depth) as i32) * 138 / 10
+fn hp(depth: u32) -> i32 {
+    -((depth * depth) as i32) * tunables::hp_margin() / 10
 }
 
-const fn history_lmr(history: i16) -> i16 {
-    history / 112
+fn history_lmr(history: i16) -> i16 {
+    history / tunables::history_lmr_div()
 }
 
 pub fn search<Search: SearchType>(
@@ -302,6 +308,9 @@ pub fn search<Search: SearchType>(
 
     let killers = thread.killer_moves[ply as usize];
     let mut move_gen = OrderedMoveGen::new(best_move, killers);
+    if ply == 0 {
+        move_gen.set_search_moves(&thread.search_moves);
+    }
 
     let mut moves_seen = 0;
     let mut move_exists = false;
@@ -315,6 +324,9 @@ pub fn search<Search: SearchType>(
         if Some(make_move) == skip_move {
             continue;
         }
+        if ply == 0 && thread.excluded_moves.contains(&make_move) {
+            continue;
+        }
 
         move_exists = true;
         let is_capture = pos.is_capture(make_move);
@@ -471,6 +483,17 @@ pub fn search<Search: SearchType>(
             }
         }
 
+        if ply == 0 {
+            if let Some(gui_info) = &thread.gui_info {
+                if shared_context.elapsed() > REPORT_DELAY {
+                    let mut uci_move = make_move;
+                    uci::convert_move_to_uci(&mut uci_move, pos.board(), thread.chess960);
+                    let move_number = thread.excluded_moves.len() + moves_seen + 1;
+                    gui_info.print_cur_move(depth, uci_move, move_number);
+                }
+            }
+        }
+
         thread.ss[ply as usize].move_played = Some(MoveData::from_move(pos.board(), make_move));
         pos.make_move_fetch(make_move, |board| {
             shared_context.get_t_table().prefetch(&board)
@@ -632,7 +655,9 @@ pub fn search<Search: SearchType>(
     }
     let highest_score = highest_score.unwrap();
 
-    if skip_move.is_none() && !thread.abort {
+    let partial_root =
+        ply == 0 && !(thread.excluded_moves.is_empty() && thread.search_moves.is_empty());
+    if skip_move.is_none() && !partial_root && !thread.abort {
         let entry_type = match () {
             _ if highest_score <= initial_alpha => Bounds::UpperBound,
             _ if highest_score >= beta => Bounds::LowerBound,
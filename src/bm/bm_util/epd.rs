@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use cozy_chess::{Board, Move};
+
+use super::san::{self, SanError};
+
+#[derive(Debug, Clone)]
+pub enum EpdError {
+    InvalidPosition(String),
+    /// Quoted operand without a closing quote
+    UnterminatedString,
+    InvalidMove(SanError),
+}
+
+impl Display for EpdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EpdError::InvalidPosition(fen) => write!(f, "invalid position {}", fen),
+            EpdError::UnterminatedString => write!(f, "unterminated string"),
+            EpdError::InvalidMove(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Extended Position Description record
+#[derive(Debug, Clone)]
+pub struct Epd {
+    pub board: Board,
+    /// Opcodes with their operands in order of appearance, quotes removed from strings
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+/// Splits operations on `;` and operands on whitespace, keeping quoted strings whole
+fn parse_operations(input: &str) -> Result<Vec<(String, Vec<String>)>, EpdError> {
+    let mut operations = vec![];
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            ';' => {
+                if !tokens.is_empty() {
+                    let opcode = tokens.remove(0);
+                    operations.push((opcode, std::mem::take(&mut tokens)));
+                }
+            }
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(char) => string.push(char),
+                        None => return Err(EpdError::UnterminatedString),
+                    }
+                }
+                tokens.push(string);
+            }
+            char if char.is_whitespace() => {}
+            char => {
+                let mut token = char.to_string();
+                while let Some(&char) = chars.peek() {
+                    if char.is_whitespace() || char == ';' || char == '"' {
+                        break;
+                    }
+                    token.push(char);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    if !tokens.is_empty() {
+        let opcode = tokens.remove(0);
+        operations.push((opcode, tokens));
+    }
+    Ok(operations)
+}
+
+impl Epd {
+    /// Parses a record, the half and full move counters are taken from `hmvc` and `fmvn` if given
+    pub fn parse(line: &str) -> Result<Self, EpdError> {
+        let mut rest = line.trim();
+        let mut position = vec![];
+        for _ in 0..4 {
+            let (field, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() {
+                return Err(EpdError::InvalidPosition(line.to_string()));
+            }
+            position.push(field);
+            rest = remainder.trim_start();
+        }
+        let operations = parse_operations(rest)?;
+        let counter = |opcode: &str, default: &str| {
+            operations
+                .iter()
+                .find(|(name, _)| name == opcode)
+                .and_then(|(_, operands)| operands.first().cloned())
+                .unwrap_or(default.to_string())
+        };
+        let fen = format!(
+            "{} {} {}",
+            position.join(" "),
+            counter("hmvc", "0"),
+            counter("fmvn", "1")
+        );
+        let board = fen
+            .parse()
+            .map_err(|_| EpdError::InvalidPosition(fen.clone()))?;
+        Ok(Self { board, operations })
+    }
+
+    /// Operands of the first operation with the given opcode
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// First operand of the given opcode, such as the string of `id` or `c0`
+    pub fn string(&self, opcode: &str) -> Option<&str> {
+        self.operands(opcode)?.first().map(String::as_str)
+    }
+
+    /// Moves of an operation with SAN operands such as `bm` and `am`
+    pub fn moves(&self, opcode: &str) -> Result<Vec<Move>, EpdError> {
+        self.operands(opcode)
+            .unwrap_or_default()
+            .iter()
+            .map(|san| san::parse_san(&self.board, san).map_err(EpdError::InvalidMove))
+            .collect()
+    }
+}
+
+#[test]
+fn parse_records() {
+    let epd = Epd::parse(
+        r#"1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - bm Qd1+; id "BK.01"; c0 "Qd1+=10, Qc6=3";"#,
+    )
+    .unwrap();
+    assert_eq!(epd.string("id"), Some("BK.01"));
+    assert_eq!(epd.string("c0"), Some("Qd1+=10, Qc6=3"));
+    assert_eq!(epd.moves("bm").unwrap()[0].to_string(), "d6d1");
+    assert!(epd.moves("am").unwrap().is_empty());
+
+    let epd = Epd::parse("8/8/8/8/8/8/8/K6k  w - -  hmvc 12; fmvn 40; am Kb2 Kb1;").unwrap();
+    assert_eq!(epd.board.halfmove_clock(), 12);
+    assert_eq!(epd.board.fullmove_number(), 40);
+    assert_eq!(epd.moves("am").unwrap().len(), 2);
+
+    assert!(Epd::parse("8/8/8 w - -").is_err());
+    assert!(Epd::parse(r#"8/8/8/8/8/8/8/K6k w - - id "open;"#).is_err());
+    assert!(Epd::parse("8/8/8/8/8/8/8/K6k w - - bm Qh8;").is_ok());
+    assert!(Epd::parse("8/8/8/8/8/8/8/K6k w - - bm Qh8;")
+        .unwrap()
+        .moves("bm")
+        .is_err());
+}
@@ -1,9 +1,15 @@
+pub mod epd;
 pub mod eval;
 pub mod frc;
 pub mod history;
 pub mod lookup;
+pub mod pgn;
+pub mod polyglot;
 pub mod position;
+pub mod san;
 pub mod t_table;
 mod table_types;
 mod threats;
+pub mod tunables;
 pub mod window;
+pub mod xor_shift;
@@ -0,0 +1,535 @@
+use std::fmt::{Display, Write};
+
+use cozy_chess::{Board, Color, Move};
+
+use super::eval::Evaluation;
+use super::san::{self, SanError};
+
+/// Movetext lines are wrapped before this many characters
+const LINE_WIDTH: usize = 80;
+
+#[derive(Debug, Clone)]
+pub enum PgnError {
+    InvalidTag(String),
+    InvalidPosition(String),
+    InvalidMove(SanError),
+    /// `$` not followed by a number from 0 to 255
+    InvalidNag(String),
+    UnterminatedComment,
+    /// `(` without a matching `)`, or a variation without a move to replace
+    InvalidVariation,
+    /// Movetext ended without a result
+    MissingResult,
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::InvalidTag(tag) => write!(f, "invalid tag {}", tag),
+            PgnError::InvalidPosition(fen) => write!(f, "invalid position {}", fen),
+            PgnError::InvalidMove(err) => write!(f, "{}", err),
+            PgnError::InvalidNag(nag) => write!(f, "invalid NAG {}", nag),
+            PgnError::UnterminatedComment => write!(f, "unterminated comment"),
+            PgnError::InvalidVariation => write!(f, "invalid variation"),
+            PgnError::MissingResult => write!(f, "missing result"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnMove {
+    pub make_move: Move,
+    /// Numeric annotation glyphs, `!` and `?` suffixes are stored as their NAG
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    /// Alternatives to this move, each starting from the position before it
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(make_move: Move) -> Self {
+        Self {
+            make_move,
+            nags: vec![],
+            comment: None,
+            variations: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgnGame {
+    /// Tag pairs in order of appearance
+    pub tags: Vec<(String, String)>,
+    /// Start position, taken from the `FEN` tag if present
+    pub board: Board,
+    /// Comment before the first move
+    pub comment: Option<String>,
+    pub moves: Vec<PgnMove>,
+}
+
+/// Comment holding an eval from the perspective of the side to move and the search depth,
+/// such as `+0.35/12` or `-M3/20`
+pub fn eval_comment(eval: Evaluation, depth: u32) -> String {
+    match eval.mate_in() {
+        Some(mate_in) if mate_in < 0 => format!("-M{}/{}", -mate_in, depth),
+        Some(mate_in) => format!("+M{}/{}", mate_in, depth),
+        None => format!("{:+.2}/{}", eval.raw() as f32 / 100.0, depth),
+    }
+}
+
+fn nag_from_suffix(suffix: &str) -> Option<u8> {
+    Some(match suffix {
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => return None,
+    })
+}
+
+fn is_result(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+enum Token {
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+/// Splits movetext into tokens, dropping move numbers
+fn tokenize(movetext: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = vec![];
+    let mut chars = movetext.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(char) => comment.push(char),
+                        None => return Err(PgnError::UnterminatedComment),
+                    }
+                }
+                tokens.push(Token::Comment(
+                    comment.split_whitespace().collect::<Vec<_>>().join(" "),
+                ));
+            }
+            ';' => {
+                let comment = chars
+                    .by_ref()
+                    .take_while(|&char| char != '\n')
+                    .collect::<String>();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => tokens.push(Token::VariationStart),
+            ')' => tokens.push(Token::VariationEnd),
+            char if char.is_whitespace() => {}
+            char => {
+                let mut word = char.to_string();
+                while let Some(&char) = chars.peek() {
+                    if char.is_whitespace() || "{}();".contains(char) {
+                        break;
+                    }
+                    word.push(char);
+                    chars.next();
+                }
+                if let Some(nag) = word.strip_prefix('$') {
+                    let nag = nag
+                        .parse()
+                        .map_err(|_| PgnError::InvalidNag(word.to_string()))?;
+                    tokens.push(Token::Nag(nag));
+                    continue;
+                }
+                if is_result(&word) {
+                    tokens.push(Token::Result(word));
+                    continue;
+                }
+                // Move numbers such as `12.`, `12...` or `12.e4`
+                let number_end = word.trim_start_matches(|char: char| char.is_ascii_digit());
+                let word = match number_end.starts_with('.') {
+                    true => number_end.trim_start_matches('.'),
+                    false => &word,
+                };
+                if word.is_empty() {
+                    continue;
+                }
+                let san = word.trim_end_matches(['!', '?']);
+                tokens.push(Token::Move(san.to_string()));
+                if let Some(nag) = nag_from_suffix(&word[san.len()..]) {
+                    tokens.push(Token::Nag(nag));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Moves of the main line or a variation
+struct Line {
+    /// Comment before the first move
+    comment: Option<String>,
+    moves: Vec<PgnMove>,
+    /// Game termination marker, only ends the main line
+    result: Option<String>,
+}
+
+/// Parses a line of moves from `board` until the end of the enclosing variation
+fn parse_line(
+    tokens: &mut std::vec::IntoIter<Token>,
+    mut board: Board,
+    keep_variations: bool,
+    top_level: bool,
+) -> Result<Line, PgnError> {
+    let mut comment = None;
+    let mut moves: Vec<PgnMove> = vec![];
+    let mut previous = board.clone();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Move(san) => {
+                let make_move = san::parse_san(&board, &san).map_err(PgnError::InvalidMove)?;
+                previous = board.clone();
+                board.play_unchecked(make_move);
+                moves.push(PgnMove::new(make_move));
+            }
+            Token::Nag(nag) => {
+                if let Some(last) = moves.last_mut() {
+                    last.nags.push(nag);
+                }
+            }
+            Token::Comment(text) => {
+                let target = match moves.last_mut() {
+                    Some(last) => &mut last.comment,
+                    None => &mut comment,
+                };
+                match target {
+                    Some(existing) => {
+                        existing.push(' ');
+                        existing.push_str(&text);
+                    }
+                    None => *target = Some(text),
+                }
+            }
+            Token::VariationStart => {
+                let variation = parse_line(tokens, previous.clone(), keep_variations, false)?;
+                let last = moves.last_mut().ok_or(PgnError::InvalidVariation)?;
+                // Empty variations such as `( )` carry no moves and are dropped
+                if keep_variations && !variation.moves.is_empty() {
+                    last.variations.push(variation.moves);
+                }
+            }
+            Token::VariationEnd if !top_level => {
+                return Ok(Line {
+                    comment,
+                    moves,
+                    result: None,
+                })
+            }
+            Token::VariationEnd => return Err(PgnError::InvalidVariation),
+            Token::Result(result) if top_level => {
+                return Ok(Line {
+                    comment,
+                    moves,
+                    result: Some(result),
+                })
+            }
+            Token::Result(_) => return Err(PgnError::InvalidVariation),
+        }
+    }
+    match top_level {
+        true => Err(PgnError::MissingResult),
+        false => Err(PgnError::InvalidVariation),
+    }
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), PgnError> {
+    let invalid = || PgnError::InvalidTag(line.to_string());
+    let inner = line
+        .trim()
+        .strip_prefix('[')
+        .and_then(|inner| inner.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let (name, value) = inner.split_once(char::is_whitespace).ok_or_else(invalid)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    Ok((
+        name.to_string(),
+        value.replace("\\\"", "\"").replace("\\\\", "\\"),
+    ))
+}
+
+impl PgnGame {
+    /// Game from `board` with the seven tag roster set to unknown values
+    #[cfg(any(test, feature = "data"))]
+    pub fn new(board: Board) -> Self {
+        let mut game = Self {
+            tags: ["Event", "Site", "Date", "Round", "White", "Black", "Result"]
+                .iter()
+                .map(|&name| (name.to_string(), "?".to_string()))
+                .collect(),
+            board: Board::default(),
+            comment: None,
+            moves: vec![],
+        };
+        game.set_tag("Date", "????.??.??");
+        game.set_tag("Result", "*");
+        game.set_board(board);
+        game
+    }
+
+    /// Sets the start position along with the `SetUp` and `FEN` tags
+    #[cfg(any(test, feature = "data"))]
+    pub fn set_board(&mut self, board: Board) {
+        self.tags
+            .retain(|(name, _)| name != "SetUp" && name != "FEN");
+        if board != Board::default() {
+            self.set_tag("SetUp", "1");
+            self.set_tag("FEN", &board.to_string());
+        }
+        self.board = board;
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Replaces the value of a tag or appends it if it doesn't exist
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, existing)) => *existing = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Sets the `Result` tag from the score of white
+    #[cfg(any(test, feature = "data"))]
+    pub fn set_result(&mut self, white_score: f32) {
+        let result = match white_score {
+            _ if white_score > 0.5 => "1-0",
+            _ if white_score < 0.5 => "0-1",
+            _ => "1/2-1/2",
+        };
+        self.set_tag("Result", result);
+    }
+
+    /// Parses a single game, variations are discarded unless `keep_variations` is set
+    pub fn parse(input: &str, keep_variations: bool) -> Result<Self, PgnError> {
+        let mut tags = vec![];
+        let mut movetext = String::new();
+        for line in input.lines() {
+            if line.trim_start().starts_with('[') && movetext.trim().is_empty() {
+                tags.push(parse_tag(line)?);
+            } else if !line.starts_with('%') {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+        let fen = tags.iter().find(|(name, _)| name == "FEN");
+        let board = match fen {
+            Some((_, fen)) => Board::from_fen(fen, false)
+                .or_else(|_| Board::from_fen(fen, true))
+                .map_err(|_| PgnError::InvalidPosition(fen.to_string()))?,
+            None => Board::default(),
+        };
+        let mut tokens = tokenize(&movetext)?.into_iter();
+        let line = parse_line(&mut tokens, board.clone(), keep_variations, true)?;
+        let mut game = Self {
+            tags,
+            board,
+            comment: line.comment,
+            moves: line.moves,
+        };
+        if let (None, Some(result)) = (game.tag("Result"), line.result) {
+            game.set_tag("Result", &result);
+        }
+        Ok(game)
+    }
+
+    /// Parses every game of a collection, a game failing to parse doesn't affect the others
+    pub fn parse_all(input: &str, keep_variations: bool) -> Vec<Result<Self, PgnError>> {
+        let mut games = vec![];
+        let mut current = String::new();
+        let mut in_movetext = false;
+        for line in input.lines() {
+            let is_tag = line.trim_start().starts_with('[');
+            if is_tag && in_movetext {
+                games.push(Self::parse(&current, keep_variations));
+                current.clear();
+                in_movetext = false;
+            }
+            in_movetext |= !is_tag && !line.trim().is_empty();
+            current.push_str(line);
+            current.push('\n');
+        }
+        if !current.trim().is_empty() {
+            games.push(Self::parse(&current, keep_variations));
+        }
+        games
+    }
+}
+
+/// Writes movetext tokens while wrapping lines
+struct MovetextWriter {
+    output: String,
+    line_len: usize,
+    /// Opening parentheses attached to the next token
+    prefix: String,
+}
+
+impl MovetextWriter {
+    fn push(&mut self, token: &str) {
+        let token = std::mem::take(&mut self.prefix) + token;
+        if self.line_len > 0 && self.line_len + 1 + token.len() >= LINE_WIDTH {
+            self.output.push('\n');
+            self.line_len = 0;
+        } else if self.line_len > 0 {
+            self.output.push(' ');
+            self.line_len += 1;
+        }
+        self.output.push_str(&token);
+        self.line_len += token.len();
+    }
+
+    fn write_line(&mut self, mut board: Board, moves: &[PgnMove]) {
+        let mut needs_number = true;
+        for pgn_move in moves {
+            let number = board.fullmove_number();
+            match board.side_to_move() {
+                Color::White => self.push(&format!("{}.", number)),
+                Color::Black if needs_number => self.push(&format!("{}...", number)),
+                Color::Black => {}
+            }
+            self.push(&san::to_san(&board, pgn_move.make_move));
+            for nag in &pgn_move.nags {
+                self.push(&format!("${}", nag));
+            }
+            needs_number = false;
+            if let Some(comment) = &pgn_move.comment {
+                self.push(&format!("{{{}}}", comment));
+                needs_number = true;
+            }
+            for variation in pgn_move.variations.iter().filter(|moves| !moves.is_empty()) {
+                self.prefix.push('(');
+                self.write_line(board.clone(), variation);
+                self.output.push(')');
+                self.line_len += 1;
+                needs_number = true;
+            }
+            board.play_unchecked(pgn_move.make_move);
+        }
+    }
+}
+
+impl Display for PgnGame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+        let mut writer = MovetextWriter {
+            output: String::new(),
+            line_len: 0,
+            prefix: String::new(),
+        };
+        if let Some(comment) = &self.comment {
+            writer.push(&format!("{{{}}}", comment));
+        }
+        writer.write_line(self.board.clone(), &self.moves);
+        writer.push(self.tag("Result").unwrap_or("*"));
+        writer.output.write_char('\n')?;
+        f.write_str(&writer.output)
+    }
+}
+
+#[test]
+fn parse_and_write() {
+    let pgn = r#"[Event "Test \"quoted\""]
+[Site "?"]
+[Result "1-0"]
+
+{Opening} 1. e4 e5 $1 2. Nf3 {main} (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6?!
+3. Bb5 ; Ruy Lopez
+a6 4. O-O 1-0
+
+[Event "Second"]
+[SetUp "1"]
+[FEN "7k/8/8/8/8/8/8/R6K w - - 0 1"]
+
+1. Ra8# 1-0
+"#;
+    let games = PgnGame::parse_all(pgn, true);
+    assert_eq!(games.len(), 2);
+    let game = games[0].as_ref().unwrap();
+    assert_eq!(game.tag("Event"), Some("Test \"quoted\""));
+    assert_eq!(game.comment.as_deref(), Some("Opening"));
+    assert_eq!(game.moves.len(), 7);
+    assert_eq!(game.moves[1].nags, [1]);
+    assert_eq!(game.moves[2].comment.as_deref(), Some("main"));
+    assert_eq!(game.moves[2].variations[0].len(), 3);
+    assert_eq!(game.moves[2].variations[0][1].variations[0].len(), 1);
+    assert_eq!(game.moves[3].nags, [6]);
+    assert_eq!(game.moves[4].comment.as_deref(), Some("Ruy Lopez"));
+    assert_eq!(game.moves[6].make_move.to_string(), "e1h1");
+    let game = games[1].as_ref().unwrap();
+    assert_eq!(game.board.side_to_move(), Color::White);
+    assert_eq!(game.moves.len(), 1);
+
+    let written = games[0].as_ref().unwrap().to_string();
+    assert!(written.contains("[Event \"Test \\\"quoted\\\"\"]"));
+    assert!(written
+        .contains("{Opening} 1. e4 e5 $1 2. Nf3 {main} (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 $6"));
+    let reparsed = PgnGame::parse(&written, true).unwrap();
+    assert_eq!(reparsed.moves, games[0].as_ref().unwrap().moves);
+    assert_eq!(
+        PgnGame::parse(&written, false).unwrap().moves[2]
+            .variations
+            .len(),
+        0
+    );
+
+    assert!(matches!(
+        PgnGame::parse("1. e4 e5", false),
+        Err(PgnError::MissingResult)
+    ));
+    assert!(matches!(
+        PgnGame::parse("1. e4 (1. d4 *", false),
+        Err(PgnError::InvalidVariation)
+    ));
+    assert!(matches!(
+        PgnGame::parse("1. e5 *", false),
+        Err(PgnError::InvalidMove(_))
+    ));
+    assert!(matches!(
+        PgnGame::parse("1. e4 $256 *", false),
+        Err(PgnError::InvalidNag(nag)) if nag == "$256"
+    ));
+    let game = PgnGame::parse("1. e4 ( ) e5 *", true).unwrap();
+    assert!(game.moves[0].variations.is_empty());
+    assert!(game.to_string().ends_with(
+        "
+1. e4 e5 *
+"
+    ));
+
+    let mut game = PgnGame::new(Board::default());
+    let mut pgn_move = PgnMove::new("e2e4".parse().unwrap());
+    pgn_move.comment = Some(eval_comment(Evaluation::new(35), 12));
+    pgn_move.variations.push(vec![]);
+    game.moves.push(pgn_move);
+    game.set_result(0.5);
+    assert!(game.to_string().ends_with("\n1. e4 {+0.35/12} 1/2-1/2\n"));
+}
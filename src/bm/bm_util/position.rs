@@ -1,6 +1,6 @@
 use cozy_chess::{BitBoard, Board, Color, GameStatus, Move, Piece};
 
-use crate::bm::nnue::Nnue;
+use crate::bm::nnue::{Nnue, NnueMismatch};
 
 use super::{eval::Evaluation, frc, threats::threats};
 
@@ -47,6 +47,12 @@ impl Position {
         self.last_eval = 0;
     }
 
+    /// Replaces the evaluation network, forces recalculation of NNUE accumulators
+    pub fn set_evaluator(&mut self, evaluator: Nnue) {
+        self.evaluator = evaluator;
+        self.reset();
+    }
+
     /// Forces recalculation of NNUE accumulators
     pub fn reset(&mut self) {
         self.evaluator
@@ -194,6 +200,33 @@ impl Position {
         }
     }
 
+    /// Compares the incrementally updated NNUE accumulators against a full refresh
+    ///
+    /// If they differ, the feature changes of the last move are checked to find the diverging feature
+    pub fn verify_nnue(&mut self) -> Result<(), NnueMismatch> {
+        self.update_nnue();
+        let mut reference = self.evaluator.clone();
+        reference.full_reset(&self.current, self.w_threats, self.b_threats);
+        let Some(mismatch) = self.evaluator.accumulator_mismatch(&reference) else {
+            return Ok(());
+        };
+        if let (Some(Some(last_mv)), Some(board), Some(&old_threats)) =
+            (self.moves.last(), self.boards.last(), self.threats.last())
+        {
+            let feature_mismatch = self.evaluator.move_feature_mismatch(
+                board,
+                &self.current,
+                *last_mv,
+                (self.w_threats, self.b_threats),
+                old_threats,
+            );
+            if let Some(feature_mismatch) = feature_mismatch {
+                return Err(feature_mismatch);
+            }
+        }
+        Err(mismatch)
+    }
+
     /// Returns aggression value
     /// - Value may vary depending on position and root evaluation
     /// - Avoid storing, instead recalculate for a given position
@@ -0,0 +1,297 @@
+use std::fmt::Display;
+
+use cozy_chess::{Board, File, GameStatus, Move, Piece, Rank, Square};
+
+#[derive(Debug, Clone)]
+pub enum SanError {
+    /// Not a move in standard algebraic notation
+    Invalid(String),
+    /// No legal move matches
+    Illegal(String),
+    /// More than one legal move matches
+    Ambiguous(String),
+}
+
+impl Display for SanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SanError::Invalid(san) => write!(f, "invalid move {}", san),
+            SanError::Illegal(san) => write!(f, "illegal move {}", san),
+            SanError::Ambiguous(san) => write!(f, "ambiguous move {}", san),
+        }
+    }
+}
+
+fn piece_from_char(piece: char) -> Option<Piece> {
+    Some(match piece {
+        'N' => Piece::Knight,
+        'B' => Piece::Bishop,
+        'R' => Piece::Rook,
+        'Q' => Piece::Queen,
+        'K' => Piece::King,
+        _ => return None,
+    })
+}
+
+fn is_castle(board: &Board, make_move: Move) -> bool {
+    board.colors(board.side_to_move()).has(make_move.to)
+}
+
+/// Parses a move in standard algebraic notation, castling is `O-O`/`O-O-O`
+/// - Check, mate and annotation suffixes are ignored
+pub fn parse_san(board: &Board, san: &str) -> Result<Move, SanError> {
+    let invalid = || SanError::Invalid(san.to_string());
+    let stripped = san.trim_end_matches(['+', '#', '!', '?']);
+    let color = board.side_to_move();
+
+    let mut moves = vec![];
+    board.generate_moves(|piece_moves| {
+        moves.extend(piece_moves);
+        false
+    });
+
+    let castle = match stripped {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(short) = castle {
+        let rights = board.castle_rights(color);
+        let rook = match short {
+            true => rights.short,
+            false => rights.long,
+        };
+        let king = board.king(color);
+        return moves
+            .into_iter()
+            .find(|&make_move| {
+                make_move.from == king
+                    && is_castle(board, make_move)
+                    && Some(make_move.to.file()) == rook
+            })
+            .ok_or_else(|| SanError::Illegal(san.to_string()));
+    }
+
+    let (body, promotion) = match stripped.split_once('=') {
+        Some((body, promotion)) => {
+            let mut chars = promotion.chars();
+            let piece = chars.next().and_then(piece_from_char).ok_or_else(invalid)?;
+            if chars.next().is_some() {
+                return Err(invalid());
+            }
+            (body, Some(piece))
+        }
+        None => match stripped.chars().last().and_then(piece_from_char) {
+            // Promotion without `=` such as `e8Q`
+            Some(piece) if stripped.len() > 2 => (&stripped[..stripped.len() - 1], Some(piece)),
+            _ => (stripped, None),
+        },
+    };
+    let mut chars = body.chars().collect::<Vec<_>>();
+    let piece = match chars.first().copied().and_then(piece_from_char) {
+        Some(piece) => {
+            chars.remove(0);
+            piece
+        }
+        None => Piece::Pawn,
+    };
+    if chars.len() < 2 {
+        return Err(invalid());
+    }
+    let to = chars.split_off(chars.len() - 2);
+    let to = Square::new(
+        File::try_from(to[0]).map_err(|_| invalid())?,
+        Rank::try_from(to[1]).map_err(|_| invalid())?,
+    );
+    if chars.last() == Some(&'x') {
+        chars.pop();
+    }
+    let mut from_file = None;
+    let mut from_rank = None;
+    for disambiguation in chars {
+        if let Ok(file) = File::try_from(disambiguation) {
+            from_file = Some(file);
+        } else if let Ok(rank) = Rank::try_from(disambiguation) {
+            from_rank = Some(rank);
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    let mut matching = moves.into_iter().filter(|&make_move| {
+        board.piece_on(make_move.from) == Some(piece)
+            && make_move.to == to
+            && !is_castle(board, make_move)
+            && make_move.promotion == promotion
+            && from_file.is_none_or(|file| make_move.from.file() == file)
+            && from_rank.is_none_or(|rank| make_move.from.rank() == rank)
+    });
+    match (matching.next(), matching.next()) {
+        (Some(make_move), None) => Ok(make_move),
+        (Some(_), Some(_)) => Err(SanError::Ambiguous(san.to_string())),
+        (None, _) => Err(SanError::Illegal(san.to_string())),
+    }
+}
+
+fn piece_char(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn => 'P',
+        Piece::Knight => 'N',
+        Piece::Bishop => 'B',
+        Piece::Rook => 'R',
+        Piece::Queen => 'Q',
+        Piece::King => 'K',
+    }
+}
+
+/// Formats a legal move in standard algebraic notation
+/// - Castling is written as `O-O`/`O-O-O`, also in Chess960
+/// - Checks and mates are suffixed with `+` and `#`
+pub fn to_san(board: &Board, make_move: Move) -> String {
+    let piece = board.piece_on(make_move.from).unwrap();
+    let mut san = String::new();
+    if piece == Piece::King && is_castle(board, make_move) {
+        san.push_str(match make_move.to.file() > make_move.from.file() {
+            true => "O-O",
+            false => "O-O-O",
+        });
+    } else {
+        let capture = board.colors(!board.side_to_move()).has(make_move.to)
+            || (piece == Piece::Pawn && make_move.from.file() != make_move.to.file());
+        if piece == Piece::Pawn {
+            if capture {
+                san.push(make_move.from.file().into());
+            }
+        } else {
+            san.push(piece_char(piece));
+            let mut others = vec![];
+            board.generate_moves_for(board.pieces(piece), |piece_moves| {
+                others.extend(piece_moves.into_iter().filter(|&other| {
+                    other.to == make_move.to
+                        && other.from != make_move.from
+                        && !is_castle(board, other)
+                }));
+                false
+            });
+            if !others.is_empty() {
+                let same_file = others
+                    .iter()
+                    .any(|other| other.from.file() == make_move.from.file());
+                let same_rank = others
+                    .iter()
+                    .any(|other| other.from.rank() == make_move.from.rank());
+                if !same_file {
+                    san.push(make_move.from.file().into());
+                } else if !same_rank {
+                    san.push(make_move.from.rank().into());
+                } else {
+                    san.push_str(&make_move.from.to_string());
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&make_move.to.to_string());
+        if let Some(promotion) = make_move.promotion {
+            san.push('=');
+            san.push(piece_char(promotion));
+        }
+    }
+
+    let mut child = board.clone();
+    child.play_unchecked(make_move);
+    if !child.checkers().is_empty() {
+        san.push(match child.status() {
+            GameStatus::Won => '#',
+            _ => '+',
+        });
+    }
+    san
+}
+
+/// Formats a line of legal moves starting from `board` in standard algebraic notation
+pub fn line_to_san(board: &Board, moves: &[Move]) -> Vec<String> {
+    let mut board = board.clone();
+    moves
+        .iter()
+        .map(|&make_move| {
+            let san = to_san(&board, make_move);
+            board.play_unchecked(make_move);
+            san
+        })
+        .collect()
+}
+
+#[test]
+fn parse_moves() {
+    let board: Board = "r3k2r/pPpp1ppp/8/4p3/8/2N3N1/P1PPPPPP/R3K2R w KQkq - 0 1"
+        .parse()
+        .unwrap();
+    let parsed = [
+        ("O-O", "e1h1"),
+        ("O-O-O+", "e1a1"),
+        ("Nce4", "c3e4"),
+        ("Ngxe4", "g3e4"),
+        ("bxa8=Q", "b7a8q"),
+        ("b8N#", "b7b8n"),
+        ("b8=R!?", "b7b8r"),
+        ("a4", "a2a4"),
+    ];
+    for (san, expected) in parsed {
+        assert_eq!(
+            parse_san(&board, san).unwrap().to_string(),
+            expected,
+            "{}",
+            san
+        );
+    }
+    for ambiguous in ["Ne4", "N3e4"] {
+        assert!(matches!(
+            parse_san(&board, ambiguous),
+            Err(SanError::Ambiguous(_))
+        ));
+    }
+    assert!(matches!(
+        parse_san(&board, "Nd6"),
+        Err(SanError::Illegal(_))
+    ));
+    assert!(matches!(parse_san(&board, "b8"), Err(SanError::Illegal(_))));
+    assert!(matches!(
+        parse_san(&board, "Zz9"),
+        Err(SanError::Invalid(_))
+    ));
+}
+
+#[test]
+fn format_moves() {
+    let board: Board = "r3k2r/pPpp1ppp/8/4p3/8/2N3N1/P1PPPPPP/R3K2R w KQkq - 0 1"
+        .parse()
+        .unwrap();
+    let formatted = [
+        ("e1h1", "O-O"),
+        ("e1a1", "O-O-O"),
+        ("c3e4", "Nce4"),
+        ("b7a8q", "bxa8=Q+"),
+        ("b7b8n", "b8=N"),
+        ("a2a4", "a4"),
+    ];
+    for (make_move, expected) in formatted {
+        let make_move = make_move.parse().unwrap();
+        let san = to_san(&board, make_move);
+        assert_eq!(san, expected);
+        assert_eq!(parse_san(&board, &san).unwrap(), make_move);
+    }
+
+    let board: Board = "k7/8/8/8/1N3N2/8/1N3N2/K7 w - - 0 1".parse().unwrap();
+    assert_eq!(to_san(&board, "b2d3".parse().unwrap()), "Nb2d3");
+    assert_eq!(to_san(&board, "b4a6".parse().unwrap()), "Na6");
+    assert_eq!(to_san(&board, "f4d5".parse().unwrap()), "Nfd5");
+    assert_eq!(to_san(&board, "b4d5".parse().unwrap()), "Nbd5");
+    let board: Board = "k7/8/8/8/1N6/8/1N6/K7 w - - 0 1".parse().unwrap();
+    assert_eq!(to_san(&board, "b4d3".parse().unwrap()), "N4d3");
+
+    let board: Board = "1k6/8/8/3Pp3/8/8/8/RK6 w A e6 0 1".parse().unwrap();
+    let line = ["d5e6", "b8a7", "b1a1", "a7a8"].map(|make_move| make_move.parse().unwrap());
+    assert_eq!(line_to_san(&board, &line), ["dxe6", "Ka7", "O-O-O", "Ka8"]);
+}
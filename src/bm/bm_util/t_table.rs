@@ -1,3 +1,5 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
 
 use cozy_chess::{Board, Move, Piece, Square};
@@ -311,6 +313,22 @@ impl TranspositionTable {
         new_depth * 2 + self.age_of(prev) as u32 + 1 >= prev_depth
     }
 
+    /// Permille of sampled entries that were written to since the last [age](Self::age)
+    pub fn hashfull(&self) -> u32 {
+        let age = self.age.load(Ordering::Relaxed);
+        let sample = &self.table[..self.table.len().min(1000)];
+        let filled = sample
+            .iter()
+            .filter(|entry| {
+                let entry_a = entry.analysis[0].load(Ordering::Relaxed);
+                let entry_b = entry.analysis[1].load(Ordering::Relaxed);
+                let entry_u64 = entry_a as u64 | ((entry_b as u64) << 32);
+                Analysis::from_raw(entry_u64).is_some_and(|analysis| analysis.age == age)
+            })
+            .count();
+        (filled * 1000 / sample.len().max(1)) as u32
+    }
+
     pub fn clean(&self) {
         self.age.store(0, Ordering::Relaxed);
         self.table.iter().for_each(|entry| entry.zero());
@@ -319,4 +337,128 @@ impl TranspositionTable {
     pub fn age(&self) {
         self.age.fetch_add(1, Ordering::Relaxed);
     }
+
+    /// Writes the table size, age and every entry to a file
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(SAVE_MAGIC)?;
+        writer.write_all(&(self.table.len() as u64).to_le_bytes())?;
+        writer.write_all(&[self.age.load(Ordering::Relaxed)])?;
+        for entry in self.table.iter() {
+            writer.write_all(&entry.hash.load(Ordering::Relaxed).to_le_bytes())?;
+            for analysis in &entry.analysis {
+                writer.write_all(&analysis.load(Ordering::Relaxed).to_le_bytes())?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Reads a table written by [save](Self::save), the table has the size it was saved with
+    pub fn load(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != *SAVE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a transposition table file",
+            ));
+        }
+        let mut len = [0; 8];
+        reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+        let expected_len = len
+            .checked_mul(ENTRY_BYTES)
+            .and_then(|bytes| bytes.checked_add(SAVE_HEADER_BYTES));
+        if expected_len != Some(file_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "transposition table file is truncated",
+            ));
+        }
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "transposition table file has no entries",
+            ));
+        }
+        if len > MAX_SAVE_ENTRIES || usize::try_from(len).is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "transposition table file is too large",
+            ));
+        }
+        let mut age = [0; 1];
+        reader.read_exact(&mut age)?;
+
+        let mut table = Vec::with_capacity(len as usize);
+        let mut bytes = [0; ENTRY_BYTES as usize];
+        for _ in 0..len {
+            reader.read_exact(&mut bytes)?;
+            let mut words = bytes
+                .chunks(4)
+                .map(|word| AtomicU32::new(u32::from_le_bytes(word.try_into().unwrap())));
+            table.push(Entry {
+                hash: words.next().unwrap(),
+                analysis: [words.next().unwrap(), words.next().unwrap()],
+            });
+        }
+        Ok(Self {
+            table: table.into_boxed_slice(),
+            age: AtomicU8::new(age[0]),
+        })
+    }
+}
+
+const SAVE_MAGIC: &[u8; 4] = b"BMTT";
+/// Magic, entry count and age
+const SAVE_HEADER_BYTES: u64 = 4 + 8 + 1;
+/// Hash and two analysis words
+const ENTRY_BYTES: u64 = 12;
+/// Entries of the largest table the `Hash` option allows
+const MAX_SAVE_ENTRIES: u64 = 65536 * 1024 * 1024 / ENTRY_BYTES;
+
+#[test]
+fn save_and_load() {
+    let t_table = TranspositionTable::new(1024);
+    let board = Board::default();
+    let make_move = "e2e4".parse().unwrap();
+    t_table.age();
+    t_table.set(
+        &board,
+        12,
+        Bounds::Exact,
+        Evaluation::new(35),
+        Some(make_move),
+        Evaluation::new(20),
+    );
+
+    let path = std::env::temp_dir().join("bm_t_table_save_and_load.bin");
+    let path = path.to_str().unwrap();
+    t_table.save(path).unwrap();
+    let loaded = TranspositionTable::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(loaded.table.len(), t_table.table.len());
+    assert_eq!(loaded.get(&board), t_table.get(&board));
+    assert_eq!(loaded.get(&board).unwrap().table_move, Some(make_move));
+    assert_eq!(loaded.hashfull(), t_table.hashfull());
+}
+
+#[test]
+fn load_invalid_header() {
+    let path = std::env::temp_dir().join("bm_t_table_load_invalid_header.bin");
+    let path = path.to_str().unwrap();
+    for len in [u64::MAX, u64::MAX / ENTRY_BYTES, 1024, 0] {
+        let mut bytes = SAVE_MAGIC.to_vec();
+        bytes.extend(len.to_le_bytes());
+        bytes.push(0);
+        std::fs::write(path, bytes).unwrap();
+        let err = TranspositionTable::load(path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+    std::fs::remove_file(path).unwrap();
 }
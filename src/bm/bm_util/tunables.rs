@@ -0,0 +1,190 @@
+//! Search parameters, constants unless the `tune` feature is enabled
+//!
+//! With `tune` every parameter is stored in a [Tunable] registry, exposed
+//! as a UCI spin option and can be printed as an SPSA config
+
+#[cfg(feature = "tune")]
+use std::fmt::Write;
+#[cfg(feature = "tune")]
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Parameter with the range and step SPSA tunes it with
+#[cfg(feature = "tune")]
+pub struct Tunable {
+    pub name: &'static str,
+    pub default: i32,
+    pub min: i32,
+    pub max: i32,
+    /// Perturbation size at the end of tuning
+    pub step: i32,
+    value: AtomicI32,
+}
+
+#[cfg(feature = "tune")]
+impl Tunable {
+    const fn new(name: &'static str, default: i32, min: i32, max: i32, step: i32) -> Self {
+        Self {
+            name,
+            default,
+            min,
+            max,
+            step,
+            value: AtomicI32::new(default),
+        }
+    }
+
+    pub fn get(&self) -> i32 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    /// Sets the value, clamped to the range of the parameter
+    pub fn set(&self, value: i32) {
+        self.value
+            .store(value.clamp(self.min, self.max), Ordering::Relaxed);
+    }
+}
+
+macro_rules! tunables {
+    ($($(#[$meta:meta])* $name:ident: $ty:ty = $default:literal, $min:literal..=$max:literal, $step:literal;)*) => {
+        #[cfg(feature = "tune")]
+        #[allow(non_upper_case_globals)]
+        mod values {
+            use super::Tunable;
+
+            $(pub static $name: Tunable = Tunable::new(stringify!($name), $default, $min, $max, $step);)*
+        }
+
+        /// Every parameter in declaration order
+        #[cfg(feature = "tune")]
+        pub static TUNABLES: &[&Tunable] = &[$(&values::$name),*];
+
+        $(
+            $(#[$meta])*
+            #[cfg(feature = "tune")]
+            #[inline]
+            pub fn $name() -> $ty {
+                values::$name.get() as $ty
+            }
+
+            $(#[$meta])*
+            #[cfg(not(feature = "tune"))]
+            #[inline]
+            pub const fn $name() -> $ty {
+                $default
+            }
+        )*
+    };
+}
+
+tunables! {
+    /// Reverse futility margin per ply of depth
+    rev_fp_margin: i16 = 71, 30..=150, 6;
+    /// Reverse futility margin reduction when improving
+    rev_fp_improving: i16 = 62, 0..=120, 6;
+    /// Razoring margin per ply of depth
+    razor_margin: i16 = 306, 100..=600, 20;
+    /// Margin below alpha of the razoring verification search
+    razor_qsearch: i16 = 277, 100..=500, 15;
+    /// Minimum null move reduction
+    nmp_base: u32 = 4, 2..=6, 1;
+    /// Null move reduction per 60 plies of depth
+    nmp_depth_factor: u32 = 23, 10..=40, 2;
+    /// Eval above beta per additional ply of null move reduction
+    nmp_eval_div: i16 = 204, 100..=400, 15;
+    /// Futility margin per ply of reduced depth
+    fp_margin: i16 = 86, 40..=160, 6;
+    /// SEE pruning margin per ply of depth
+    see_fp_margin: i16 = 123, 50..=200, 8;
+    /// History pruning threshold per squared ply of depth in tenths
+    hp_margin: i32 = 138, 50..=250, 10;
+    /// History score per ply of LMR reduction
+    history_lmr_div: i16 = 112, 50..=250, 8;
+    /// LMR base reduction in hundredths
+    lmr_base: i32 = 50, 0..=150, 8;
+    /// LMR divisor in hundredths
+    lmr_div: i32 = 205, 100..=400, 10;
+    /// Late move pruning base move count in hundredths
+    lmp_base: i32 = 297, 100..=600, 20;
+    /// Late move pruning move count divisor when not improving in hundredths
+    lmp_improving_div: i32 = 194, 100..=400, 10;
+    /// Initial aspiration window size
+    window_start: i16 = 15, 5..=40, 2;
+    /// Aspiration window growth in hundredths of the window
+    window_factor: i16 = 45, 10..=100, 5;
+    /// Constant aspiration window growth
+    window_add: i16 = 9, 0..=30, 2;
+    /// Time scale base, reduced by one for each iteration the best move stays the same
+    tm_stability_base: u32 = 41, 15..=60, 2;
+    /// Time scale per move stability point in thousandths
+    tm_stability_scale: i32 = 24, 10..=40, 2;
+    /// Time scale for the share of nodes outside the best move in hundredths
+    tm_node_scale: i32 = 342, 150..=500, 15;
+    /// Minimum node time scale in hundredths
+    tm_node_base: i32 = 52, 20..=100, 4;
+    /// Time scale per centipawn of eval drop in thousandths
+    tm_eval_scale: i32 = 88, 50..=130, 5;
+}
+
+#[cfg(feature = "tune")]
+pub fn find(name: &str) -> Option<&'static Tunable> {
+    TUNABLES
+        .iter()
+        .copied()
+        .find(|tunable| tunable.name.eq_ignore_ascii_case(name))
+}
+
+/// SPSA config in the OpenBench format, one `name, int, value, min, max, c_end, r_end` line per parameter
+#[cfg(feature = "tune")]
+pub fn openbench_config() -> String {
+    let mut config = String::new();
+    for tunable in TUNABLES {
+        writeln!(
+            &mut config,
+            "{}, int, {}, {}, {}, {}, 0.002",
+            tunable.name,
+            tunable.get(),
+            tunable.min,
+            tunable.max,
+            tunable.step
+        )
+        .unwrap();
+    }
+    config
+}
+
+/// SPSA config in the weather-factory `config.json` format
+#[cfg(feature = "tune")]
+pub fn weather_factory_config() -> String {
+    let entries = TUNABLES
+        .iter()
+        .map(|tunable| {
+            format!(
+                "    \"{}\": {{\n        \"value\": {},\n        \"min_value\": {},\n        \"max_value\": {},\n        \"step\": {}\n    }}",
+                tunable.name,
+                tunable.get(),
+                tunable.min,
+                tunable.max,
+                tunable.step
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+#[cfg(feature = "tune")]
+#[test]
+fn registry() {
+    for tunable in TUNABLES {
+        assert!(
+            tunable.min <= tunable.default && tunable.default <= tunable.max,
+            "{}",
+            tunable.name
+        );
+        assert!(tunable.step > 0, "{}", tunable.name);
+    }
+    let config = openbench_config();
+    assert_eq!(config.lines().count(), TUNABLES.len());
+    assert!(config.starts_with("rev_fp_margin, int, 71, 30, 150, 6, 0.002"));
+    assert!(find("RAZOR_MARGIN").is_some());
+    assert!(find("unknown").is_none());
+}
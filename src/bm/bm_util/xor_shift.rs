@@ -0,0 +1,32 @@
+/// Xorshift generator for debugging tools, states only need to be varied, not unpredictable
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a value in `0..max`
+    pub fn below(&mut self, max: usize) -> usize {
+        (self.next_u64() % max as u64) as usize
+    }
+
+    /// Returns true with a probability of `1 / denominator`
+    pub fn chance(&mut self, denominator: usize) -> bool {
+        self.below(denominator) == 0
+    }
+
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> Option<T> {
+        match items.is_empty() {
+            true => None,
+            false => Some(items[self.below(items.len())]),
+        }
+    }
+}
@@ -0,0 +1,529 @@
+use std::fmt::Display;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use cozy_chess::{Board, Move};
+
+use crate::bm::bm_runner::ab_runner::{self, AbRunner};
+use crate::bm::bm_runner::config::{GuiInfo, NoInfo, Run, SearchInfo};
+use crate::bm::bm_runner::time::{TimeManagementInfo, TimeManager};
+use crate::bm::bm_util::eval::Evaluation;
+use crate::bm::nnue::{NetworkError, Nnue};
+use crate::bm::uci;
+
+/// Search limits, unset limits don't restrict the search
+///
+/// At least one limit should be set, a search without limits only ends once it reaches the maximum depth
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub move_time: Option<Duration>,
+    pub w_time: Option<Duration>,
+    pub b_time: Option<Duration>,
+    pub w_inc: Option<Duration>,
+    pub b_inc: Option<Duration>,
+    pub moves_to_go: Option<u32>,
+    /// Search for a mate in at most the given amount of moves
+    pub mate: Option<u32>,
+    /// Search until stopped, only useful with [search_async](Engine::search_async)
+    pub infinite: bool,
+    /// Root moves to restrict the search to, all moves are searched if empty
+    pub search_moves: Vec<Move>,
+}
+
+impl SearchLimits {
+    fn time_management_info(&self) -> Vec<TimeManagementInfo> {
+        let mut info = vec![];
+        info.extend(self.depth.map(TimeManagementInfo::MaxDepth));
+        info.extend(self.nodes.map(TimeManagementInfo::MaxNodes));
+        info.extend(self.move_time.map(TimeManagementInfo::MoveTime));
+        info.extend(self.w_time.map(TimeManagementInfo::WTime));
+        info.extend(self.b_time.map(TimeManagementInfo::BTime));
+        info.extend(self.w_inc.map(TimeManagementInfo::WInc));
+        info.extend(self.b_inc.map(TimeManagementInfo::BInc));
+        info.extend(self.moves_to_go.map(TimeManagementInfo::MovesToGo));
+        info.extend(self.mate.map(TimeManagementInfo::Mate));
+        if self.infinite {
+            info.push(TimeManagementInfo::Infinite);
+        }
+        info
+    }
+}
+
+/// Side to move relative score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// Centipawns
+    Cp(i16),
+    /// Moves until mate, negative if the side to move is getting mated
+    Mate(i16),
+}
+
+impl From<Evaluation> for Score {
+    fn from(eval: Evaluation) -> Self {
+        match eval.mate_in() {
+            Some(mate_in) => Score::Mate(mate_in),
+            None => Score::Cp(eval.raw()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Move,
+    /// Expected reply to the best move
+    pub ponder_move: Option<Move>,
+    pub score: Score,
+    /// Win, draw and loss permille for the side to move
+    pub wdl: (u16, u16, u16),
+    /// Principal variation starting with the best move
+    pub pv: Vec<Move>,
+    /// Last completed depth
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum EngineError {
+    /// Move isn't legal in the position it's played in
+    IllegalMove(Move),
+    /// Searched position is checkmate or stalemate
+    NoLegalMoves,
+}
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::IllegalMove(make_move) => write!(f, "illegal move {}", make_move),
+            EngineError::NoLegalMoves => write!(f, "no legal moves"),
+        }
+    }
+}
+
+/// Forwards search progress to the [Engine](Engine::set_gui_info) callback and a [SearchHandle]
+struct ForwardInfo {
+    gui_info: Arc<dyn GuiInfo>,
+    sender: Mutex<Sender<SearchInfo>>,
+}
+
+impl GuiInfo for ForwardInfo {
+    fn print_info(&self, info: &SearchInfo) {
+        self.gui_info.print_info(info);
+        let _ = self.sender.lock().unwrap().send(info.clone());
+    }
+
+    fn print_cur_move(&self, depth: u32, make_move: Move, move_number: usize) {
+        self.gui_info.print_cur_move(depth, make_move, move_number);
+    }
+}
+
+/// Search running on a separate thread, see [Engine::search_async]
+pub struct SearchHandle {
+    time_manager: Arc<TimeManager>,
+    receiver: Receiver<SearchInfo>,
+    /// Only [None] if the search failed to start, such handles aren't returned
+    thread: JoinHandle<Option<SearchResult>>,
+}
+
+impl SearchHandle {
+    /// Requests the search to end as soon as possible, the result is still available through [join](Self::join)
+    pub fn stop(&self) {
+        self.time_manager.abort_now();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Progress reported since the last call, doesn't block
+    /// - Includes fail high and fail low reports, see [SearchInfo::bounds]
+    pub fn try_updates(&self) -> impl Iterator<Item = SearchInfo> + '_ {
+        self.receiver.try_iter()
+    }
+
+    /// Blocks on each progress report, ends once the search is finished
+    pub fn updates(&self) -> impl Iterator<Item = SearchInfo> + '_ {
+        self.receiver.iter()
+    }
+
+    /// Waits for the search to finish
+    pub fn join(self) -> SearchResult {
+        self.thread
+            .join()
+            .unwrap()
+            .expect("handle of a search that didn't start")
+    }
+}
+
+/// Library interface to the engine
+///
+/// Moves are given and returned in UCI notation, castling is written as the king
+/// moving two squares unless [Chess960](Self::set_chess960) is enabled
+///
+/// Methods called while an [asynchronous search](Self::search_async) runs block until it finishes
+pub struct Engine {
+    runner: Arc<Mutex<AbRunner>>,
+    time_manager: Arc<TimeManager>,
+    gui_info: Arc<dyn GuiInfo>,
+    chess960: bool,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        let time_manager = Arc::new(TimeManager::new());
+        Self {
+            runner: Arc::new(Mutex::new(AbRunner::new(
+                Board::default(),
+                time_manager.clone(),
+            ))),
+            time_manager,
+            gui_info: Arc::new(NoInfo),
+            chess960: false,
+        }
+    }
+
+    /// Sets the position to `board` followed by `moves`, the position is unchanged if a move is illegal
+    pub fn set_position(&mut self, board: Board, moves: &[Move]) -> Result<(), EngineError> {
+        let mut current = board.clone();
+        let mut converted = vec![];
+        for &make_move in moves {
+            let mut make_move = make_move;
+            uci::convert_move(&mut make_move, &current, self.chess960);
+            if !current.is_legal(make_move) {
+                return Err(EngineError::IllegalMove(make_move));
+            }
+            current.play_unchecked(make_move);
+            converted.push(make_move);
+        }
+        let runner = &mut *self.runner.lock().unwrap();
+        runner.set_board(board);
+        for make_move in converted {
+            runner.make_move(make_move);
+        }
+        Ok(())
+    }
+
+    /// Plays a move on the current position, the position is unchanged if the move is illegal
+    pub fn make_move(&mut self, make_move: Move) -> Result<(), EngineError> {
+        let runner = &mut *self.runner.lock().unwrap();
+        let mut make_move = make_move;
+        uci::convert_move(&mut make_move, runner.get_board(), self.chess960);
+        if !runner.get_board().is_legal(make_move) {
+            return Err(EngineError::IllegalMove(make_move));
+        }
+        runner.make_move(make_move);
+        Ok(())
+    }
+
+    pub fn board(&self) -> Board {
+        self.runner.lock().unwrap().get_board().clone()
+    }
+
+    /// Receives progress of every following search
+    pub fn set_gui_info(&mut self, gui_info: Arc<dyn GuiInfo>) {
+        self.gui_info = gui_info;
+    }
+
+    /// Clears the transposition table
+    pub fn new_game(&mut self) {
+        self.runner.lock().unwrap().new_game();
+    }
+
+    pub fn set_hash(&mut self, hash_mb: usize) {
+        self.runner.lock().unwrap().hash(hash_mb);
+    }
+
+    /// Total search threads, at least 1
+    pub fn set_threads(&mut self, threads: u8) {
+        self.runner.lock().unwrap().set_threads(threads.max(1));
+    }
+
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.runner.lock().unwrap().set_multi_pv(multi_pv.max(1));
+    }
+
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+        self.runner.lock().unwrap().set_chess960(chess960);
+    }
+
+    /// Reports win, draw and loss permille in [SearchInfo]
+    pub fn set_show_wdl(&mut self, show_wdl: bool) {
+        self.runner.lock().unwrap().set_uci_show_wdl(show_wdl);
+    }
+
+    pub fn set_move_overhead(&mut self, move_overhead: Duration) {
+        self.time_manager.set_move_overhead(move_overhead);
+    }
+
+    /// Replaces the embedded network
+    pub fn load_network(&mut self, path: &str) -> Result<(), NetworkError> {
+        let nnue = Nnue::from_file(path)?;
+        self.runner.lock().unwrap().set_network(nnue);
+        Ok(())
+    }
+
+    /// Searches the current position until a limit is reached, blocking the caller
+    /// - Fails without searching if the position has no legal moves
+    pub fn search(&mut self, limits: &SearchLimits) -> Result<SearchResult, EngineError> {
+        let runner = &mut *self.runner.lock().unwrap();
+        let board = prepare_search(runner, &self.time_manager, limits, self.chess960)?;
+        Ok(finish_search(
+            runner,
+            &self.time_manager,
+            self.gui_info.clone(),
+            board,
+            self.chess960,
+        ))
+    }
+
+    /// Searches the current position on a separate thread
+    /// - Fails without starting a search if the position has no legal moves
+    pub fn search_async(&mut self, limits: &SearchLimits) -> Result<SearchHandle, EngineError> {
+        let (sender, receiver) = mpsc::channel();
+        let gui_info = Arc::new(ForwardInfo {
+            gui_info: self.gui_info.clone(),
+            sender: Mutex::new(sender),
+        });
+        let chess960 = self.chess960;
+        let limits = limits.clone();
+        let runner = self.runner.clone();
+        let time_manager = self.time_manager.clone();
+        let (prepared_sender, prepared) = mpsc::channel();
+        // The runner stays locked from preparing until the search ends so the position can't change
+        // in between, time management is initiated before returning so an immediate stop isn't reset
+        let thread = std::thread::spawn(move || {
+            let runner = &mut *runner.lock().unwrap();
+            let board = match prepare_search(runner, &time_manager, &limits, chess960) {
+                Ok(board) => board,
+                Err(err) => {
+                    let _ = prepared_sender.send(Err(err));
+                    return None;
+                }
+            };
+            let _ = prepared_sender.send(Ok(()));
+            Some(finish_search(
+                runner,
+                &time_manager,
+                gui_info,
+                board,
+                chess960,
+            ))
+        });
+        prepared.recv().unwrap()?;
+        let time_manager = self.time_manager.clone();
+        Ok(SearchHandle {
+            time_manager,
+            receiver,
+            thread,
+        })
+    }
+}
+
+/// Applies search moves and initiates time management, returns the root position
+fn prepare_search(
+    runner: &mut AbRunner,
+    time_manager: &TimeManager,
+    limits: &SearchLimits,
+    chess960: bool,
+) -> Result<Board, EngineError> {
+    let board = runner.get_board().clone();
+    if !board.generate_moves(|_| true) {
+        return Err(EngineError::NoLegalMoves);
+    }
+    let mut search_moves = limits.search_moves.clone();
+    for make_move in &mut search_moves {
+        uci::convert_move(make_move, &board, chess960);
+    }
+    search_moves.retain(|&make_move| board.is_legal(make_move));
+    runner.set_search_moves(search_moves);
+
+    time_manager.initiate(&board, &limits.time_management_info());
+    Ok(board)
+}
+
+fn finish_search(
+    runner: &mut AbRunner,
+    time_manager: &TimeManager,
+    gui_info: Arc<dyn GuiInfo>,
+    board: Board,
+    chess960: bool,
+) -> SearchResult {
+    let (best_move, eval, depth, nodes) = runner.search::<Run>(gui_info);
+    time_manager.clear();
+
+    let eval = ab_runner::reported_eval(&board, eval);
+    let (win, loss, draw) = ab_runner::to_wld(eval);
+    let mut pv = runner.pv().to_vec();
+    if pv.first() != Some(&best_move) {
+        pv = vec![best_move];
+    }
+    let ponder_move = runner.ponder_move();
+
+    let mut current = board;
+    let mut uci_pv = vec![];
+    for make_move in pv {
+        let mut uci_move = make_move;
+        uci::convert_move_to_uci(&mut uci_move, &current, chess960);
+        current.play_unchecked(make_move);
+        uci_pv.push(uci_move);
+    }
+    let ponder_move = match uci_pv.get(1) {
+        Some(&ponder_move) => Some(ponder_move),
+        None => ponder_move.map(|mut ponder_move| {
+            uci::convert_move_to_uci(&mut ponder_move, &current, chess960);
+            ponder_move
+        }),
+    };
+    SearchResult {
+        best_move: uci_pv[0],
+        ponder_move,
+        score: eval.into(),
+        wdl: (win as u16, draw as u16, loss as u16),
+        pv: uci_pv,
+        depth: depth.saturating_sub(1),
+        nodes,
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn search_position() {
+    let mut engine = Engine::new();
+    let moves = ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6"];
+    let moves = moves.map(|make_move| make_move.parse().unwrap());
+    engine.set_position(Board::default(), &moves).unwrap();
+    assert!(engine
+        .set_position(Board::default(), &["e2e5".parse().unwrap()])
+        .is_err());
+    assert_eq!(engine.board().fullmove_number(), 4);
+
+    let limits = SearchLimits {
+        depth: Some(6),
+        ..Default::default()
+    };
+    let result = engine.search(&limits).unwrap();
+    assert_eq!(result.pv[0], result.best_move);
+    assert!(result.depth >= 6);
+    assert!(result.nodes > 0);
+
+    let mut board = engine.board().clone();
+    for &make_move in &result.pv {
+        let mut make_move = make_move;
+        uci::convert_move(&mut make_move, &board, false);
+        assert!(board.is_legal(make_move));
+        board.play_unchecked(make_move);
+    }
+}
+
+#[test]
+fn make_moves() {
+    let mut engine = Engine::new();
+    for make_move in ["e2e4", "e7e5", "g1f3"] {
+        engine.make_move(make_move.parse().unwrap()).unwrap();
+    }
+    assert!(engine.make_move("e1g1".parse().unwrap()).is_err());
+    let board = engine.board();
+    let moves = ["e2e4", "e7e5", "g1f3"].map(|make_move| make_move.parse().unwrap());
+    engine.set_position(Board::default(), &moves).unwrap();
+    assert_eq!(engine.board(), board);
+}
+
+#[test]
+fn stop_async_search() {
+    let mut engine = Engine::new();
+    let limits = SearchLimits {
+        infinite: true,
+        ..Default::default()
+    };
+    let handle = engine.search_async(&limits).unwrap();
+    let first = handle.updates().next().unwrap();
+    assert_eq!(first.depth, 1);
+    assert!(!handle.is_finished());
+
+    handle.stop();
+    let result = handle.join();
+    assert!(engine.board().is_legal(result.best_move));
+}
+
+#[test]
+fn duplicate_search_moves() {
+    let mut engine = Engine::new();
+    engine.set_multi_pv(2);
+    let e4 = "e2e4".parse().unwrap();
+    let limits = SearchLimits {
+        depth: Some(4),
+        search_moves: vec![e4, e4],
+        ..Default::default()
+    };
+    let result = engine.search(&limits).unwrap();
+    assert_eq!(result.best_move, e4);
+}
+
+#[test]
+fn set_position_during_async_search() {
+    let mut engine = Engine::new();
+    let board = engine.board();
+    let limits = SearchLimits {
+        depth: Some(6),
+        ..Default::default()
+    };
+    let handle = engine.search_async(&limits).unwrap();
+    let other = "7k/8/6K1/8/8/8/8/5Q2 w - - 0 1".parse::<Board>().unwrap();
+    engine.set_position(other.clone(), &[]).unwrap();
+    assert_eq!(engine.board(), other);
+
+    let result = handle.join();
+    let mut current = board;
+    for &make_move in &result.pv {
+        let mut make_move = make_move;
+        uci::convert_move(&mut make_move, &current, false);
+        assert!(current.is_legal(make_move));
+        current.play_unchecked(make_move);
+    }
+}
+
+#[test]
+fn search_without_legal_moves() {
+    let mut engine = Engine::new();
+    let limits = SearchLimits {
+        depth: Some(4),
+        ..Default::default()
+    };
+    for fen in [
+        "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+    ] {
+        engine.set_position(fen.parse().unwrap(), &[]).unwrap();
+        assert!(matches!(
+            engine.search(&limits),
+            Err(EngineError::NoLegalMoves)
+        ));
+        assert!(matches!(
+            engine.search_async(&limits),
+            Err(EngineError::NoLegalMoves)
+        ));
+    }
+}
+
+#[test]
+fn mated_result() {
+    let mut engine = Engine::new();
+    let board = "7k/5K2/8/8/8/8/8/6R1 b - - 0 1".parse().unwrap();
+    engine.set_position(board, &[]).unwrap();
+    let limits = SearchLimits {
+        depth: Some(10),
+        ..Default::default()
+    };
+    let result = engine.search(&limits).unwrap();
+    assert!(matches!(result.score, Score::Mate(mate_in) if mate_in <= 0));
+    assert_eq!(result.wdl, (0, 0, 1000));
+}
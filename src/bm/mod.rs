@@ -2,5 +2,7 @@ pub mod bm_console;
 pub mod bm_runner;
 pub mod bm_search;
 pub mod bm_util;
+pub mod engine;
 pub mod nnue;
 pub mod uci;
+pub mod xboard;
@@ -9,6 +9,7 @@ use super::bm_runner::ab_runner;
 
 mod include;
 mod layers;
+pub mod verify;
 
 include!(concat!(env!("OUT_DIR"), "/arch.rs"));
 
@@ -92,6 +93,76 @@ fn threat_indices(perspective: Color, king: Square, sq: Square, color: Color) ->
     Update::new(index, perspective)
 }
 
+/// Indices of all features active in a position from a perspective
+fn active_features(
+    perspective: Color,
+    board: &Board,
+    w_threats: BitBoard,
+    b_threats: BitBoard,
+) -> Vec<usize> {
+    let king = board.king(perspective);
+    let mut features = vec![];
+    for sq in board.occupied() {
+        let piece = board.piece_on(sq).unwrap();
+        let color = board.color_on(sq).unwrap();
+        features.push(halfka_feature(perspective, king, color, piece, sq));
+    }
+    for sq in w_threats {
+        features.push(threat_feature(perspective, king, Color::Black, sq));
+    }
+    for sq in b_threats {
+        features.push(threat_feature(perspective, king, Color::White, sq));
+    }
+    features
+}
+
+/// Difference between incrementally updated and fully refreshed network state
+#[derive(Debug, Clone)]
+pub enum NnueMismatch {
+    /// A move changed a feature by a different amount than a refresh would
+    /// - Change is 1 for an added feature and -1 for a removed feature
+    Feature {
+        perspective: Color,
+        index: usize,
+        expected: i32,
+        found: i32,
+    },
+    /// Accumulator value differs from a refresh
+    Accumulator {
+        perspective: Color,
+        index: usize,
+        expected: i16,
+        found: i16,
+    },
+}
+
+impl std::fmt::Display for NnueMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NnueMismatch::Feature {
+                perspective,
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} feature {} changed by {}, expected {}",
+                perspective, index, found, expected
+            ),
+            NnueMismatch::Accumulator {
+                perspective,
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{:?} accumulator {} is {}, expected {}",
+                perspective, index, found, expected
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Nnue {
     accumulator: Vec<Accumulator>,
@@ -110,9 +181,87 @@ pub struct Nnue {
     null_moves: Vec<bool>,
 }
 
+/// Size of the header containing the layer sizes of a network file
+const HEADER_BYTES: usize = 12;
+
+/// Size of a network file matching the compiled architecture
+const NETWORK_BYTES: usize =
+    HEADER_BYTES + INPUT * MID * 2 + MID * 2 + MID * OUTPUT * 2 + OUTPUT * 2;
+
+#[derive(Debug)]
+pub enum NetworkError {
+    Io(std::io::Error),
+    /// File is too short to contain a header
+    MissingHeader,
+    /// Layer sizes in the header don't match the compiled architecture
+    Architecture {
+        expected: [usize; 3],
+        found: [usize; 3],
+    },
+    /// File size doesn't match the size implied by the architecture
+    Size {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl std::fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkError::Io(err) => write!(f, "{}", err),
+            NetworkError::MissingHeader => write!(f, "file is too short to contain a header"),
+            NetworkError::Architecture { expected, found } => write!(
+                f,
+                "architecture {}x{}x{} doesn't match the compiled architecture {}x{}x{}",
+                found[0], found[1], found[2], expected[0], expected[1], expected[2]
+            ),
+            NetworkError::Size { expected, found } => {
+                write!(f, "expected {} bytes, found {} bytes", expected, found)
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for NetworkError {
+    fn from(err: std::io::Error) -> Self {
+        NetworkError::Io(err)
+    }
+}
+
 impl Nnue {
+    /// Embedded network
     pub fn new() -> Self {
-        let mut bytes = &NN_BYTES[12..];
+        Self::from_bytes(NN_BYTES).unwrap()
+    }
+
+    /// Loads a network from a file using the same format as the embedded network
+    pub fn from_file(path: &str) -> Result<Self, NetworkError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Parses a network, the layer sizes in the header must match the compiled architecture
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, NetworkError> {
+        if bytes.len() < HEADER_BYTES {
+            return Err(NetworkError::MissingHeader);
+        }
+        let mut arch = [0; 3];
+        for (bytes, layer) in bytes.chunks(4).zip(&mut arch) {
+            *layer = u32::from_le_bytes(bytes.try_into().unwrap()) as usize;
+        }
+        if arch != [INPUT, MID, OUTPUT] {
+            return Err(NetworkError::Architecture {
+                expected: [INPUT, MID, OUTPUT],
+                found: arch,
+            });
+        }
+        if bytes.len() != NETWORK_BYTES {
+            return Err(NetworkError::Size {
+                expected: NETWORK_BYTES,
+                found: bytes.len(),
+            });
+        }
+
+        let mut bytes = &bytes[HEADER_BYTES..];
         let incremental = Arc::from(include::sparse_from_bytes_i16::<INPUT, MID>(bytes));
         bytes = &bytes[INPUT * MID * 2..];
         let incremental_bias = include::bias_from_bytes_i16::<i16, MID>(bytes);
@@ -128,7 +277,7 @@ impl Nnue {
         let input_layer = Incremental::new(incremental);
         let out_layer = Dense::new(out, out_bias);
 
-        Self {
+        Ok(Self {
             accumulator: vec![
                 Accumulator {
                     w_acc: incremental_bias,
@@ -146,7 +295,7 @@ impl Nnue {
             out_layer,
             head: 0,
             null_moves: Vec::with_capacity(ab_runner::MAX_PLY as usize + 1),
-        }
+        })
     }
 
     pub fn perform_reset_update(&mut self, color: Color) {
@@ -213,32 +362,8 @@ impl Nnue {
         w_threats: BitBoard,
         b_threats: BitBoard,
     ) {
-        for sq in board.occupied() {
-            let piece = board.piece_on(sq).unwrap();
-            let color = board.color_on(sq).unwrap();
-            self.update::<true>(piece_indices(
-                perspective,
-                board.king(perspective),
-                sq,
-                piece,
-                color,
-            ));
-        }
-        for sq in w_threats {
-            self.update::<true>(threat_indices(
-                perspective,
-                board.king(perspective),
-                sq,
-                Color::Black,
-            ));
-        }
-        for sq in b_threats {
-            self.update::<true>(threat_indices(
-                perspective,
-                board.king(perspective),
-                sq,
-                Color::White,
-            ));
+        for index in active_features(perspective, board, w_threats, b_threats) {
+            self.update::<true>(Update::new(index, perspective));
         }
 
         let acc = &mut self.accumulator[self.head];
@@ -276,8 +401,7 @@ impl Nnue {
         old_b_threats: BitBoard,
     ) {
         self.push_accumulator();
-        let from_sq = make_move.from;
-        let from_type = board.piece_on(from_sq).unwrap();
+        let from_type = board.piece_on(make_move.from).unwrap();
         let stm = board.side_to_move();
         let mut perspectives: &[Color] = &[Color::White, Color::Black];
         let single = &[!stm];
@@ -286,122 +410,218 @@ impl Nnue {
             self.reset(stm, &new_board, w_threats, b_threats);
         }
         for &perspective in perspectives {
-            for w_threat_sq in w_threats ^ old_w_threats {
-                match w_threats.has(w_threat_sq) {
-                    true => self.update::<true>(threat_indices(
-                        perspective,
-                        board.king(perspective),
-                        w_threat_sq,
-                        Color::Black,
-                    )),
-                    false => self.update::<false>(threat_indices(
-                        perspective,
-                        board.king(perspective),
-                        w_threat_sq,
-                        Color::Black,
-                    )),
-                };
-            }
+            self.push_move_updates(
+                perspective,
+                board,
+                make_move,
+                (w_threats, b_threats),
+                (old_w_threats, old_b_threats),
+            );
+            self.perform_update(perspective);
+        }
+        self.clear();
+    }
 
-            for b_threat_sq in b_threats ^ old_b_threats {
-                match b_threats.has(b_threat_sq) {
-                    true => self.update::<true>(threat_indices(
-                        perspective,
-                        board.king(perspective),
-                        b_threat_sq,
-                        Color::White,
-                    )),
-                    false => self.update::<false>(threat_indices(
-                        perspective,
-                        board.king(perspective),
-                        b_threat_sq,
-                        Color::White,
-                    )),
-                }
+    /// Queues the feature changes of a move for a perspective whose king didn't move
+    fn push_move_updates(
+        &mut self,
+        perspective: Color,
+        board: &Board,
+        make_move: Move,
+        (w_threats, b_threats): (BitBoard, BitBoard),
+        (old_w_threats, old_b_threats): (BitBoard, BitBoard),
+    ) {
+        let from_sq = make_move.from;
+        let from_type = board.piece_on(from_sq).unwrap();
+        let stm = board.side_to_move();
+        for w_threat_sq in w_threats ^ old_w_threats {
+            match w_threats.has(w_threat_sq) {
+                true => self.update::<true>(threat_indices(
+                    perspective,
+                    board.king(perspective),
+                    w_threat_sq,
+                    Color::Black,
+                )),
+                false => self.update::<false>(threat_indices(
+                    perspective,
+                    board.king(perspective),
+                    w_threat_sq,
+                    Color::Black,
+                )),
+            };
+        }
+
+        for b_threat_sq in b_threats ^ old_b_threats {
+            match b_threats.has(b_threat_sq) {
+                true => self.update::<true>(threat_indices(
+                    perspective,
+                    board.king(perspective),
+                    b_threat_sq,
+                    Color::White,
+                )),
+                false => self.update::<false>(threat_indices(
+                    perspective,
+                    board.king(perspective),
+                    b_threat_sq,
+                    Color::White,
+                )),
             }
+        }
 
+        self.update::<false>(piece_indices(
+            perspective,
+            board.king(perspective),
+            from_sq,
+            from_type,
+            stm,
+        ));
+
+        let to_sq = make_move.to;
+        if let Some((captured, color)) = board.piece_on(to_sq).zip(board.color_on(to_sq)) {
             self.update::<false>(piece_indices(
                 perspective,
                 board.king(perspective),
-                from_sq,
-                from_type,
-                stm,
+                to_sq,
+                captured,
+                color,
             ));
+        }
 
-            let to_sq = make_move.to;
-            if let Some((captured, color)) = board.piece_on(to_sq).zip(board.color_on(to_sq)) {
+        if let Some(ep) = board.en_passant() {
+            let (stm_fifth, stm_sixth) = match stm {
+                Color::White => (Rank::Fifth, Rank::Sixth),
+                Color::Black => (Rank::Fourth, Rank::Third),
+            };
+            if from_type == Piece::Pawn && to_sq == Square::new(ep, stm_sixth) {
                 self.update::<false>(piece_indices(
                     perspective,
                     board.king(perspective),
-                    to_sq,
-                    captured,
-                    color,
+                    Square::new(ep, stm_fifth),
+                    Piece::Pawn,
+                    !stm,
                 ));
             }
-
-            if let Some(ep) = board.en_passant() {
-                let (stm_fifth, stm_sixth) = match stm {
-                    Color::White => (Rank::Fifth, Rank::Sixth),
-                    Color::Black => (Rank::Fourth, Rank::Third),
-                };
-                if from_type == Piece::Pawn && to_sq == Square::new(ep, stm_sixth) {
-                    self.update::<false>(piece_indices(
-                        perspective,
-                        board.king(perspective),
-                        Square::new(ep, stm_fifth),
-                        Piece::Pawn,
-                        !stm,
-                    ));
-                }
-            }
-            if Some(stm) == board.color_on(to_sq) {
-                let stm_first = match stm {
-                    Color::White => Rank::First,
-                    Color::Black => Rank::Eighth,
-                };
-                if to_sq.file() > from_sq.file() {
-                    self.update::<true>(piece_indices(
-                        perspective,
-                        board.king(perspective),
-                        Square::new(File::G, stm_first),
-                        Piece::King,
-                        stm,
-                    ));
-                    self.update::<true>(piece_indices(
-                        perspective,
-                        board.king(perspective),
-                        Square::new(File::F, stm_first),
-                        Piece::Rook,
-                        stm,
-                    ));
-                } else {
-                    self.update::<true>(piece_indices(
-                        perspective,
-                        board.king(perspective),
-                        Square::new(File::C, stm_first),
-                        Piece::King,
-                        stm,
-                    ));
-                    self.update::<true>(piece_indices(
-                        perspective,
-                        board.king(perspective),
-                        Square::new(File::D, stm_first),
-                        Piece::Rook,
-                        stm,
-                    ));
-                }
+        }
+        if Some(stm) == board.color_on(to_sq) {
+            let stm_first = match stm {
+                Color::White => Rank::First,
+                Color::Black => Rank::Eighth,
+            };
+            if to_sq.file() > from_sq.file() {
+                self.update::<true>(piece_indices(
+                    perspective,
+                    board.king(perspective),
+                    Square::new(File::G, stm_first),
+                    Piece::King,
+                    stm,
+                ));
+                self.update::<true>(piece_indices(
+                    perspective,
+                    board.king(perspective),
+                    Square::new(File::F, stm_first),
+                    Piece::Rook,
+                    stm,
+                ));
             } else {
                 self.update::<true>(piece_indices(
                     perspective,
                     board.king(perspective),
-                    to_sq,
-                    make_move.promotion.unwrap_or(from_type),
+                    Square::new(File::C, stm_first),
+                    Piece::King,
+                    stm,
+                ));
+                self.update::<true>(piece_indices(
+                    perspective,
+                    board.king(perspective),
+                    Square::new(File::D, stm_first),
+                    Piece::Rook,
                     stm,
                 ));
             }
-            self.perform_update(perspective);
+        } else {
+            self.update::<true>(piece_indices(
+                perspective,
+                board.king(perspective),
+                to_sq,
+                make_move.promotion.unwrap_or(from_type),
+                stm,
+            ));
         }
-        self.clear();
+    }
+
+    /// Compares the current accumulators against the current accumulators of a reference network
+    pub fn accumulator_mismatch(&self, reference: &Nnue) -> Option<NnueMismatch> {
+        let acc = &self.accumulator[self.head];
+        let reference = &reference.accumulator[reference.head];
+        let perspectives = [
+            (Color::White, &acc.w_acc, &reference.w_acc),
+            (Color::Black, &acc.b_acc, &reference.b_acc),
+        ];
+        for (perspective, acc, reference) in perspectives {
+            let diverging = acc.0.iter().zip(&reference.0).position(|(a, b)| a != b);
+            if let Some(index) = diverging {
+                return Some(NnueMismatch::Accumulator {
+                    perspective,
+                    index,
+                    expected: reference.0[index],
+                    found: acc.0[index],
+                });
+            }
+        }
+        None
+    }
+
+    /// Compares the feature changes [make_move](Self::make_move) applies incrementally
+    /// against the difference of the features active before and after the move
+    /// - Perspectives refreshed due to a king move are skipped
+    pub fn move_feature_mismatch(
+        &mut self,
+        board: &Board,
+        new_board: &Board,
+        make_move: Move,
+        threats: (BitBoard, BitBoard),
+        old_threats: (BitBoard, BitBoard),
+    ) -> Option<NnueMismatch> {
+        let stm = board.side_to_move();
+        let king_move = board.piece_on(make_move.from) == Some(Piece::King);
+        for perspective in [Color::White, Color::Black] {
+            if king_move && perspective == stm {
+                continue;
+            }
+            let mut changes = std::collections::BTreeMap::<usize, (i32, i32)>::new();
+            for index in active_features(perspective, board, old_threats.0, old_threats.1) {
+                changes.entry(index).or_default().0 -= 1;
+            }
+            for index in active_features(perspective, new_board, threats.0, threats.1) {
+                changes.entry(index).or_default().0 += 1;
+            }
+
+            self.push_move_updates(perspective, board, make_move, threats, old_threats);
+            let (add, rm) = match perspective {
+                Color::White => (&self.w_add, &self.w_rm),
+                Color::Black => (&self.b_add, &self.b_rm),
+            };
+            for &index in add {
+                changes.entry(index).or_default().1 += 1;
+            }
+            for &index in rm {
+                changes.entry(index).or_default().1 -= 1;
+            }
+            self.clear();
+
+            let diverging = changes
+                .into_iter()
+                .find(|(_, (expected, found))| expected != found);
+            if let Some((index, (expected, found))) = diverging {
+                return Some(NnueMismatch::Feature {
+                    perspective,
+                    index,
+                    expected,
+                    found,
+                });
+            }
+        }
+        None
     }
 
     pub fn unmake_move(&mut self) {
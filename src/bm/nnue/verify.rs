@@ -0,0 +1,124 @@
+use std::fmt::Display;
+
+use cozy_chess::{Board, Move, Piece};
+
+use super::NnueMismatch;
+use crate::bm::bm_runner::ab_runner::MAX_PLY;
+use crate::bm::bm_util::position::Position;
+use crate::bm::bm_util::xor_shift::XorShift;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Step {
+    Make(Move),
+    Unmake(Move),
+    Null,
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Make(make_move) => write!(f, "move {}", make_move),
+            Step::Unmake(make_move) => write!(f, "unmake {}", make_move),
+            Step::Null => write!(f, "null move"),
+        }
+    }
+}
+
+/// First step after which the incrementally updated accumulators differ from a full refresh
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// Position the step was taken from
+    pub fen: String,
+    pub step: Step,
+    pub mismatch: NnueMismatch,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} from {}: {}", self.step, self.fen, self.mismatch)
+    }
+}
+
+/// Moves that touch the special cases of incremental updates
+fn is_special(board: &Board, make_move: Move) -> bool {
+    let piece = board.piece_on(make_move.from);
+    make_move.promotion.is_some()
+        || board.occupied().has(make_move.to)
+        || piece == Some(Piece::King)
+        || (piece == Some(Piece::Pawn) && make_move.from.file() != make_move.to.file())
+}
+
+/// Plays random games from standard and Chess960 start positions through
+/// [Position::make_move], [Position::unmake_move] and [Position::null_move],
+/// comparing the accumulators against a full refresh after every step
+pub fn verify_games(games: usize, plies: usize, seed: u64) -> Option<Divergence> {
+    let mut rng = XorShift::new(seed);
+    let mut pos = Position::new(Board::default());
+    for _ in 0..games {
+        let start = match rng.chance(2) {
+            true => Board::default(),
+            false => Board::chess960_startpos(rng.below(960) as u32),
+        };
+        pos.set_board(start);
+        let mut played: Vec<Option<Move>> = vec![];
+        for _ in 0..plies {
+            let fen = format!("{}", pos.board());
+            let step = match played.last() {
+                Some(&last) if rng.chance(8) => {
+                    pos.unmake_move();
+                    played.pop();
+                    match last {
+                        Some(make_move) => Step::Unmake(make_move),
+                        None => Step::Null,
+                    }
+                }
+                _ if rng.chance(32) && pos.null_move() => {
+                    played.push(None);
+                    Step::Null
+                }
+                _ => {
+                    let board = pos.board();
+                    let mut moves = vec![];
+                    board.generate_moves(|piece_moves| {
+                        moves.extend(piece_moves);
+                        false
+                    });
+                    let special = moves
+                        .iter()
+                        .copied()
+                        .filter(|&make_move| is_special(board, make_move))
+                        .collect::<Vec<_>>();
+                    let make_move = match rng.chance(2) {
+                        true => rng.pick(&special).or_else(|| rng.pick(&moves)),
+                        false => rng.pick(&moves),
+                    };
+                    let Some(make_move) = make_move else {
+                        break;
+                    };
+                    pos.make_move(make_move);
+                    played.push(Some(make_move));
+                    Step::Make(make_move)
+                }
+            };
+            if let Err(mismatch) = pos.verify_nnue() {
+                return Some(Divergence {
+                    fen,
+                    step,
+                    mismatch,
+                });
+            }
+            if played.len() >= MAX_PLY as usize {
+                pos.set_board(pos.board().clone());
+                played.clear();
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn incremental_updates() {
+    if let Some(divergence) = verify_games(8, 256, 0x5eed) {
+        panic!("{}", divergence);
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+use std::str::FromStr;
 use std::time::Duration;
 
 use cozy_chess::{Board, Move};
@@ -9,124 +11,217 @@ pub enum UciCommand {
     IsReady,
     NewGame,
     Position(Board, Vec<Move>),
-    Go(Vec<TimeManagementInfo>),
+    Go(Vec<TimeManagementInfo>, Vec<Move>),
     SetOption(String, String),
     Move(Move),
     Bench(u32),
+    Perft(u32),
+    SaveHash(String),
+    LoadHash(String),
     Empty,
     Stop,
+    PonderHit,
     Quit,
     Eval,
     Static,
+    /// Prints the board, its FEN and the PV of the last search in SAN
+    Print,
+}
+
+#[derive(Debug, Clone)]
+pub enum UciParseError {
+    /// `position` without `startpos` or `fen`
+    MissingPosition,
+    InvalidFen(String),
+    InvalidMove(String),
+    /// Parameter given without a value
+    MissingValue(&'static str),
+    /// Parameter given with a value that can't be parsed
+    InvalidValue(&'static str, String),
+}
+
+impl Display for UciParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UciParseError::MissingPosition => write!(f, "missing startpos or fen"),
+            UciParseError::InvalidFen(fen) => write!(f, "invalid fen {}", fen),
+            UciParseError::InvalidMove(mv) => write!(f, "invalid move {}", mv),
+            UciParseError::MissingValue(name) => write!(f, "missing value for {}", name),
+            UciParseError::InvalidValue(name, value) => {
+                write!(f, "invalid value {} for {}", value, name)
+            }
+        }
+    }
+}
+
+fn parse_value<'a, T: FromStr>(
+    split: &mut impl Iterator<Item = &'a str>,
+    name: &'static str,
+) -> Result<T, UciParseError> {
+    let value = split.next().ok_or(UciParseError::MissingValue(name))?;
+    value
+        .parse()
+        .map_err(|_| UciParseError::InvalidValue(name, value.to_string()))
+}
+
+fn parse_millis<'a>(
+    split: &mut impl Iterator<Item = &'a str>,
+    name: &'static str,
+) -> Result<Duration, UciParseError> {
+    let millis = parse_value::<i64>(split, name)?;
+    Ok(Duration::from_millis(millis.max(0) as u64))
+}
+
+/// Joins the remaining tokens so paths may contain spaces
+fn parse_path<'a>(
+    split: impl Iterator<Item = &'a str>,
+    name: &'static str,
+) -> Result<String, UciParseError> {
+    let path = split.collect::<Vec<_>>().join(" ");
+    match path.is_empty() {
+        true => Err(UciParseError::MissingValue(name)),
+        false => Ok(path),
+    }
 }
 
 impl UciCommand {
-    pub fn parse(input: &str, chess960: bool) -> Self {
+    pub fn parse(input: &str, chess960: bool) -> Result<Self, UciParseError> {
         let input_move = input.parse();
         if let Ok(m) = input_move {
-            return UciCommand::Move(m);
+            return Ok(UciCommand::Move(m));
         }
         let mut split = input.split_ascii_whitespace();
         let token = match split.next() {
             Some(string) => string,
-            None => return UciCommand::Empty,
+            None => return Ok(UciCommand::Empty),
         };
-        match token {
+        Ok(match token {
             "uci" => UciCommand::Uci,
             "ucinewgame" => UciCommand::NewGame,
             "position" => {
-                let mut board = String::new();
-                let mut chess_board = None;
-                let split = split.into_iter().collect::<Vec<_>>();
-
-                let mut board_end = 0;
-                for (index, token) in split.iter().enumerate() {
-                    let token = token.trim();
-                    if token == "startpos" {
-                        chess_board = Some(Board::default());
-                        board_end = index + 1;
-                        break;
-                    } else if token != "fen" {
-                        if token == "moves" {
-                            if let Ok(board) = Board::from_fen(board.trim(), chess960) {
-                                chess_board = Some(board);
-                                board_end = index;
-                                break;
-                            }
-                        }
-                        board += token;
-                        board += " ";
+                let split = split.collect::<Vec<_>>();
+                let moves_start = split
+                    .iter()
+                    .position(|&token| token == "moves")
+                    .unwrap_or(split.len());
+                let board = match split.first() {
+                    None => return Err(UciParseError::MissingPosition),
+                    Some(&"startpos") => Board::default(),
+                    Some(&first) => {
+                        let fen_start = (first == "fen") as usize;
+                        let fen = split[fen_start..moves_start].join(" ");
+                        Board::from_fen(&fen, chess960)
+                            .map_err(|_| UciParseError::InvalidFen(fen))?
                     }
-                }
-                if chess_board.is_none() {
-                    chess_board = Some(Board::from_fen(board.trim(), chess960).unwrap());
-                }
+                };
                 let mut moves = vec![];
-                if board_end < split.len() && split[board_end] == "moves" {
-                    for token in &split[board_end + 1..] {
-                        let make_move = token.parse().unwrap();
-                        moves.push(make_move);
-                    }
+                for &token in split.iter().skip(moves_start + 1) {
+                    let make_move = token
+                        .parse()
+                        .map_err(|_| UciParseError::InvalidMove(token.to_string()))?;
+                    moves.push(make_move);
                 }
-                UciCommand::Position(chess_board.unwrap(), moves)
+                UciCommand::Position(board, moves)
             }
             "go" => {
                 let mut commands = vec![];
+                let mut search_moves = vec![];
+                let mut split = split.peekable();
+                if split.peek() == Some(&"perft") {
+                    split.next();
+                    return Ok(UciCommand::Perft(parse_value(&mut split, "perft")?));
+                }
                 while let Some(option) = split.next() {
                     commands.push(match option {
-                        "wtime" => {
-                            let millis = split.next().unwrap().parse::<i64>().unwrap();
-                            let millis = millis.max(0) as u64;
-                            TimeManagementInfo::WTime(Duration::from_millis(millis))
-                        }
-                        "btime" => {
-                            let millis = split.next().unwrap().parse::<i64>().unwrap();
-                            let millis = millis.max(0) as u64;
-                            TimeManagementInfo::BTime(Duration::from_millis(millis))
-                        }
-                        "winc" => {
-                            let millis = split.next().unwrap().parse().unwrap();
-                            TimeManagementInfo::WInc(Duration::from_millis(millis))
-                        }
-                        "binc" => {
-                            let millis = split.next().unwrap().parse().unwrap();
-                            TimeManagementInfo::BInc(Duration::from_millis(millis))
-                        }
+                        "wtime" => TimeManagementInfo::WTime(parse_millis(&mut split, "wtime")?),
+                        "btime" => TimeManagementInfo::BTime(parse_millis(&mut split, "btime")?),
+                        "winc" => TimeManagementInfo::WInc(parse_millis(&mut split, "winc")?),
+                        "binc" => TimeManagementInfo::BInc(parse_millis(&mut split, "binc")?),
                         "movetime" => {
-                            let millis = split.next().unwrap().parse().unwrap();
-                            TimeManagementInfo::MoveTime(Duration::from_millis(millis))
+                            TimeManagementInfo::MoveTime(parse_millis(&mut split, "movetime")?)
                         }
                         "movestogo" => {
-                            let moves_to_go = split.next().unwrap().parse().unwrap();
-                            TimeManagementInfo::MovesToGo(moves_to_go)
-                        }
-                        "depth" => {
-                            let depth = split.next().unwrap().parse().unwrap();
-                            TimeManagementInfo::MaxDepth(depth)
+                            TimeManagementInfo::MovesToGo(parse_value(&mut split, "movestogo")?)
                         }
-                        "nodes" => {
-                            let nodes = split.next().unwrap().parse().unwrap();
-                            TimeManagementInfo::MaxNodes(nodes)
+                        "depth" => TimeManagementInfo::MaxDepth(parse_value(&mut split, "depth")?),
+                        "nodes" => TimeManagementInfo::MaxNodes(parse_value(&mut split, "nodes")?),
+                        "mate" => TimeManagementInfo::Mate(parse_value(&mut split, "mate")?),
+                        "ponder" => TimeManagementInfo::Ponder,
+                        "infinite" => TimeManagementInfo::Infinite,
+                        "searchmoves" => {
+                            while let Some(make_move) =
+                                split.peek().and_then(|token| token.parse().ok())
+                            {
+                                split.next();
+                                search_moves.push(make_move);
+                            }
+                            continue;
                         }
                         _ => TimeManagementInfo::Unknown,
                     });
                 }
-                UciCommand::Go(commands)
+                UciCommand::Go(commands, search_moves)
             }
             "stop" => UciCommand::Stop,
+            "ponderhit" => UciCommand::PonderHit,
             "quit" => UciCommand::Quit,
             "eval" => UciCommand::Eval,
             "isready" => UciCommand::IsReady,
-            "bench" => UciCommand::Bench(split.next().map_or(12, |depth| depth.parse().unwrap())),
+            "bench" => match split.next() {
+                Some(depth) => UciCommand::Bench(
+                    depth
+                        .parse()
+                        .map_err(|_| UciParseError::InvalidValue("bench", depth.to_string()))?,
+                ),
+                None => UciCommand::Bench(12),
+            },
+            "perft" => UciCommand::Perft(parse_value(&mut split, "perft")?),
             "static" => UciCommand::Static,
+            "d" => UciCommand::Print,
+            "savehash" => UciCommand::SaveHash(parse_path(split, "savehash")?),
+            "loadhash" => UciCommand::LoadHash(parse_path(split, "loadhash")?),
             "setoption" => {
                 split.next();
-                let name = split.next().unwrap().to_string();
-                split.next();
-                let value = split.next().unwrap().to_string();
-                UciCommand::SetOption(name, value)
+                let mut name = vec![];
+                for token in split.by_ref() {
+                    if token == "value" {
+                        break;
+                    }
+                    name.push(token);
+                }
+                let value = split.collect::<Vec<_>>().join(" ");
+                UciCommand::SetOption(name.join(" "), value)
             }
             _ => UciCommand::Empty,
-        }
+        })
+    }
+}
+
+#[test]
+fn malformed_commands() {
+    let invalid = [
+        "position",
+        "position fen 8/8/8 w - - 0 1",
+        "position startpos moves e2e4 e7e5 xyz",
+        "go wtime",
+        "go depth ten",
+        "bench deep",
+        "perft",
+        "go perft x",
+    ];
+    for input in invalid {
+        assert!(UciCommand::parse(input, false).is_err(), "{}", input);
     }
+    assert!(matches!(
+        UciCommand::parse("setoption name Clear Hash", false),
+        Ok(UciCommand::SetOption(name, value)) if name == "Clear Hash" && value.is_empty()
+    ));
+    assert!(matches!(
+        UciCommand::parse("go perft 3", false),
+        Ok(UciCommand::Perft(3))
+    ));
+    assert!(matches!(
+        UciCommand::parse("position startpos moves e2e4 e7e5", false),
+        Ok(UciCommand::Position(_, moves)) if moves.len() == 2
+    ));
 }
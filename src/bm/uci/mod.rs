@@ -1,21 +1,35 @@
+use std::fmt::Display;
+use std::str::FromStr;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-use cozy_chess::{Board, File, Move, Piece, Square};
+use cozy_chess::{Board, Color, File, Move, Piece, Rank, Square};
 
 use crate::bm::bm_runner::ab_runner::AbRunner;
 use crate::bm::bm_runner::config::{NoInfo, Run, UciInfo};
 
-use crate::bm::bm_runner::time::{TimeManagementInfo, TimeManager};
+use crate::bm::bm_runner::time::{TimeManagementInfo, TimeManager, MOVE_OVERHEAD_DEFAULT};
+use crate::bm::bm_util::polyglot::{Book, BookSelection};
+use crate::bm::bm_util::san;
+#[cfg(feature = "tune")]
+use crate::bm::bm_util::tunables;
+use crate::bm::bm_util::xor_shift::XorShift;
+use crate::bm::nnue::Nnue;
 
-mod bench;
+pub mod bench;
 mod command;
+mod perft;
 
 use command::UciCommand;
 
 const VERSION: &str = "9.0";
 
+/// [EvalFile](UciCommand::SetOption) value selecting the embedded network
+const DEFAULT_NET: &str = "<default>";
+/// [BookFile](UciCommand::SetOption) value unloading the book
+const NO_BOOK: &str = "<empty>";
+
 enum ThreadReq {
     Go(GoReq),
     Quit,
@@ -23,7 +37,9 @@ enum ThreadReq {
 
 struct GoReq {
     bm_runner: Arc<Mutex<AbRunner>>,
+    time_manager: Arc<TimeManager>,
     chess960: bool,
+    ponder: bool,
 }
 
 pub struct UciAdapter {
@@ -34,6 +50,12 @@ pub struct UciAdapter {
     forced: bool,
     chess960: bool,
     show_wdl: bool,
+    ponder: bool,
+    /// Book moves are only played if [OwnBook](UciCommand::SetOption) is set
+    own_book: bool,
+    book: Option<Book>,
+    book_selection: BookSelection,
+    book_rng: XorShift,
 }
 
 impl UciAdapter {
@@ -50,9 +72,21 @@ impl UciAdapter {
                 match req {
                     ThreadReq::Go(req) => {
                         let mut bm_runner = req.bm_runner.lock().unwrap();
-                        let (mut best_move, _, _, _) = bm_runner.search::<Run, UciInfo>();
+                        let (mut best_move, _, _, _) = bm_runner.search::<Run>(Arc::new(UciInfo));
+                        req.time_manager.wait_for_stop();
+                        let mut ponder_move = bm_runner.ponder_move().filter(|_| req.ponder);
+                        if let Some(ponder_move) = &mut ponder_move {
+                            let mut board = bm_runner.get_board().clone();
+                            board.play_unchecked(best_move);
+                            convert_move_to_uci(ponder_move, &board, req.chess960);
+                        }
                         convert_move_to_uci(&mut best_move, bm_runner.get_board(), req.chess960);
-                        println!("bestmove {}", best_move);
+                        match ponder_move {
+                            Some(ponder_move) => {
+                                println!("bestmove {} ponder {}", best_move, ponder_move)
+                            }
+                            None => println!("bestmove {}", best_move),
+                        }
                     }
                     ThreadReq::Quit => {
                         return;
@@ -67,31 +101,73 @@ impl UciAdapter {
             time_manager,
             chess960: false,
             show_wdl: false,
+            ponder: false,
+            own_book: false,
+            book: None,
+            book_selection: BookSelection::Weighted,
+            book_rng: XorShift::new(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(1, |time| time.as_nanos() as u64),
+            ),
         }
     }
 
     pub fn input(&mut self, input: &str) -> bool {
         let name = "Black Marlin".to_string();
-        let command = UciCommand::parse(&input, self.chess960);
+        let command = match UciCommand::parse(input, self.chess960) {
+            Ok(command) => command,
+            Err(err) => {
+                println!("info string {}", err);
+                return true;
+            }
+        };
         match command {
             UciCommand::Uci => {
                 println!("id name {} {}", name, VERSION);
                 println!("id author Doruk S.");
                 println!("option name Hash type spin default 16 min 1 max 65536");
+                println!("option name Clear Hash type button");
                 println!("option name Threads type spin default 1 min 1 max 255");
+                println!(
+                    "option name Move Overhead type spin default {} min 0 max 5000",
+                    MOVE_OVERHEAD_DEFAULT.as_millis()
+                );
+                println!("option name Ponder type check default false");
+                println!("option name MultiPV type spin default 1 min 1 max 218");
+                println!("option name EvalFile type string default {}", DEFAULT_NET);
                 println!("option name UCI_ShowWDL type check default false");
                 println!("option name UCI_Chess960 type check default false");
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default {}", NO_BOOK);
+                println!(
+                    "option name BookSelection type combo default Weighted var Weighted var Best"
+                );
+                #[cfg(feature = "tune")]
+                for tunable in tunables::TUNABLES {
+                    println!(
+                        "option name {} type spin default {} min {} max {}",
+                        tunable.name, tunable.default, tunable.min, tunable.max
+                    );
+                }
                 println!("uciok");
             }
             UciCommand::IsReady => println!("readyok"),
             UciCommand::Move(make_move) => {
                 let runner = &mut *self.bm_runner.lock().unwrap();
+                if !runner.get_board().is_legal(make_move) {
+                    println!("info string illegal move {}", make_move);
+                    return true;
+                }
                 runner.make_move(make_move);
             }
             UciCommand::Empty => {}
             UciCommand::Stop => {
                 self.time_manager.abort_now();
             }
+            UciCommand::PonderHit => {
+                self.time_manager.ponder_hit();
+            }
             UciCommand::Quit => {
                 self.exit();
                 return false;
@@ -100,18 +176,32 @@ impl UciAdapter {
                 let runner = &mut *self.bm_runner.lock().unwrap();
 
                 println!("eval    : {}", runner.raw_eval().raw());
+                if !runner.pv().is_empty() {
+                    println!("pv      : {}", san_pv(runner));
+                }
             }
-            UciCommand::Go(commands) => self.go(commands),
+            UciCommand::Go(commands, search_moves) => self.go(commands, search_moves),
             UciCommand::NewGame => {
                 let runner = &mut *self.bm_runner.lock().unwrap();
                 runner.new_game();
                 runner.set_board(Board::default());
             }
             UciCommand::Position(position, moves) => {
+                let mut board = position.clone();
+                let mut legal_moves = vec![];
+                for make_move in moves {
+                    let mut legal_move = make_move;
+                    convert_move(&mut legal_move, &board, self.chess960);
+                    if !board.is_legal(legal_move) {
+                        println!("info string illegal move {}", make_move);
+                        return true;
+                    }
+                    board.play_unchecked(legal_move);
+                    legal_moves.push(legal_move);
+                }
                 let runner = &mut *self.bm_runner.lock().unwrap();
                 runner.set_board(position);
-                for mut make_move in moves {
-                    convert_move(&mut make_move, runner.get_board(), self.chess960);
+                for make_move in legal_moves {
                     runner.make_move(make_move);
                 }
             }
@@ -119,28 +209,112 @@ impl UciAdapter {
                 self.time_manager.abort_now();
                 match name.as_str() {
                     "Hash" => {
-                        self.bm_runner.lock().unwrap().hash(value.parse().unwrap());
+                        if let Some(hash) = spin_value(&name, &value, 1, 65536) {
+                            self.bm_runner.lock().unwrap().hash(hash);
+                        }
+                    }
+                    "Clear Hash" => {
+                        self.bm_runner.lock().unwrap().new_game();
                     }
                     "Threads" => {
-                        self.bm_runner
-                            .lock()
-                            .unwrap()
-                            .set_threads(value.parse().unwrap());
+                        if let Some(threads) = spin_value(&name, &value, 1, 255) {
+                            self.bm_runner.lock().unwrap().set_threads(threads);
+                        }
+                    }
+                    "Move Overhead" => {
+                        if let Some(millis) = spin_value(&name, &value, 0, 5000) {
+                            self.time_manager
+                                .set_move_overhead(Duration::from_millis(millis));
+                        }
+                    }
+                    "Ponder" => {
+                        if let Some(ponder) = check_value(&name, &value) {
+                            self.ponder = ponder;
+                        }
+                    }
+                    "MultiPV" => {
+                        if let Some(multi_pv) = spin_value(&name, &value, 1, 218) {
+                            self.bm_runner.lock().unwrap().set_multi_pv(multi_pv);
+                        }
+                    }
+                    "EvalFile" => {
+                        let nnue = match value.as_str() {
+                            DEFAULT_NET => Ok(Nnue::new()),
+                            path => Nnue::from_file(path),
+                        };
+                        match nnue {
+                            Ok(nnue) => self.bm_runner.lock().unwrap().set_network(nnue),
+                            Err(err) => {
+                                println!("info string failed to load {}: {}", value, err)
+                            }
+                        }
                     }
                     "UCI_Chess960" => {
-                        self.chess960 = value.to_lowercase().parse().unwrap();
-                        self.bm_runner.lock().unwrap().set_chess960(self.chess960);
+                        if let Some(chess960) = check_value(&name, &value) {
+                            self.chess960 = chess960;
+                            self.bm_runner.lock().unwrap().set_chess960(self.chess960);
+                        }
+                    }
+                    "OwnBook" => {
+                        if let Some(own_book) = check_value(&name, &value) {
+                            self.own_book = own_book;
+                        }
                     }
+                    "BookFile" => match value.as_str() {
+                        NO_BOOK | "" => self.book = None,
+                        path => match Book::from_file(path) {
+                            Ok(book) => self.book = Some(book),
+                            Err(err) => println!("info string failed to load {}: {}", path, err),
+                        },
+                    },
+                    "BookSelection" => match value.to_lowercase().as_str() {
+                        "weighted" => self.book_selection = BookSelection::Weighted,
+                        "best" => self.book_selection = BookSelection::Best,
+                        _ => println!("info string invalid value {} for {}", value, name),
+                    },
                     "UCI_ShowWDL" => {
-                        self.show_wdl = value.to_lowercase().parse().unwrap();
-                        self.bm_runner
-                            .lock()
-                            .unwrap()
-                            .set_uci_show_wdl(self.show_wdl);
+                        if let Some(show_wdl) = check_value(&name, &value) {
+                            self.show_wdl = show_wdl;
+                            self.bm_runner
+                                .lock()
+                                .unwrap()
+                                .set_uci_show_wdl(self.show_wdl);
+                        }
+                    }
+                    #[cfg(feature = "tune")]
+                    name => {
+                        if let Some(tunable) = tunables::find(name) {
+                            if let Some(value) = spin_value(name, &value, tunable.min, tunable.max)
+                            {
+                                tunable.set(value);
+                                self.bm_runner.lock().unwrap().reload_params();
+                            }
+                        }
                     }
+                    #[cfg(not(feature = "tune"))]
                     _ => {}
                 }
             }
+            UciCommand::Perft(depth) => {
+                let mut position = self.bm_runner.lock().unwrap().get_position().clone();
+                let start = Instant::now();
+                let divided = perft::divide(&mut position, depth, self.chess960);
+                let elapsed = start.elapsed();
+
+                for (make_move, move_nodes) in &divided {
+                    println!("{}: {}", make_move, move_nodes);
+                }
+                let nodes = match depth {
+                    0 => perft::perft(&mut position, 0),
+                    _ => divided.iter().map(|&(_, nodes)| nodes).sum(),
+                };
+                println!();
+                println!("Nodes searched: {}", nodes);
+                println!(
+                    "{} nps",
+                    (nodes as f32 / elapsed.as_secs_f32().max(f32::EPSILON)) as u64
+                );
+            }
             UciCommand::Bench(depth) => {
                 let mut bench_data = vec![];
 
@@ -154,7 +328,7 @@ impl UciAdapter {
                     let start = Instant::now();
 
                     self.time_manager.initiate(&board, &options);
-                    let (make_move, eval, _, node_cnt) = bm_runner.search::<Run, NoInfo>();
+                    let (make_move, eval, _, node_cnt) = bm_runner.search::<Run>(Arc::new(NoInfo));
                     self.time_manager.clear();
                     let elapsed = start.elapsed();
                     bench_data.push((
@@ -186,34 +360,140 @@ impl UciAdapter {
                     (sum_node_cnt as f32 / sum_time.as_secs_f32()) as u32
                 );
             }
+            UciCommand::SaveHash(path) => match self.bm_runner.lock().unwrap().save_hash(&path) {
+                Ok(()) => println!("info string saved hash to {}", path),
+                Err(err) => println!("info string failed to save hash to {}: {}", path, err),
+            },
+            UciCommand::LoadHash(path) => match self.bm_runner.lock().unwrap().load_hash(&path) {
+                Ok(()) => println!("info string loaded hash from {}", path),
+                Err(err) => {
+                    println!("info string failed to load hash from {}: {}", path, err)
+                }
+            },
             UciCommand::Static => {
                 let runner = &mut *self.bm_runner.lock().unwrap();
                 println!("{}", runner.raw_eval().raw());
             }
+            UciCommand::Print => {
+                let runner = &*self.bm_runner.lock().unwrap();
+                let board = runner.get_board();
+                for rank in Rank::ALL.into_iter().rev() {
+                    let mut line = String::new();
+                    for file in File::ALL {
+                        let square = Square::new(file, rank);
+                        let piece = match (board.piece_on(square), board.color_on(square)) {
+                            (Some(piece), Some(color)) => {
+                                let piece: char = piece.into();
+                                match color {
+                                    Color::White => piece.to_ascii_uppercase(),
+                                    Color::Black => piece,
+                                }
+                            }
+                            _ => '.',
+                        };
+                        line.push(' ');
+                        line.push(piece);
+                    }
+                    println!("{}{}", rank as u8 + 1, line);
+                }
+                println!("  a b c d e f g h");
+                println!("fen     : {}", board);
+                println!("hash    : {:016x}", board.hash());
+                if !runner.pv().is_empty() {
+                    println!("pv      : {}", san_pv(runner));
+                }
+            }
         }
         true
     }
 
-    fn go(&mut self, commands: Vec<TimeManagementInfo>) {
+    fn go(&mut self, commands: Vec<TimeManagementInfo>, mut search_moves: Vec<Move>) {
         self.forced = false;
-        self.time_manager
-            .initiate(self.bm_runner.lock().unwrap().get_board(), &commands);
+        {
+            let bm_runner = self.bm_runner.clone();
+            let runner = &mut *bm_runner.lock().unwrap();
+            for make_move in &mut search_moves {
+                convert_move(make_move, runner.get_board(), self.chess960);
+            }
+            search_moves.retain(|&make_move| runner.get_board().is_legal(make_move));
+            if let Some(mut book_move) = self.book_move(runner.get_board(), &commands) {
+                if search_moves.is_empty() || search_moves.contains(&book_move) {
+                    convert_move_to_uci(&mut book_move, runner.get_board(), self.chess960);
+                    println!("info string book move");
+                    println!("bestmove {}", book_move);
+                    return;
+                }
+            }
+            runner.set_search_moves(search_moves);
+            self.time_manager.initiate(runner.get_board(), &commands);
+        }
         let bm_runner = self.bm_runner.clone();
         let chess960 = self.chess960;
 
         let req = GoReq {
             bm_runner,
+            time_manager: self.time_manager.clone(),
             chess960,
+            ponder: self.ponder,
         };
         self.sender.send(ThreadReq::Go(req)).unwrap();
     }
 
+    /// Picks a move from the book, never while pondering, analysing or playing Chess960
+    fn book_move(&mut self, board: &Board, commands: &[TimeManagementInfo]) -> Option<Move> {
+        let analysing = commands.iter().any(|command| {
+            matches!(
+                command,
+                TimeManagementInfo::Ponder | TimeManagementInfo::Infinite
+            )
+        });
+        if !self.own_book || self.chess960 || analysing {
+            return None;
+        }
+        self.book
+            .as_ref()?
+            .pick(board, self.book_selection, &mut self.book_rng)
+    }
+
     fn exit(&mut self) {
         self.time_manager.abort_now();
         self.sender.send(ThreadReq::Quit).unwrap();
     }
 }
 
+/// Principal variation of the last search in standard algebraic notation
+fn san_pv(runner: &AbRunner) -> String {
+    san::line_to_san(runner.get_board(), runner.pv()).join(" ")
+}
+
+/// Parses a spin option value, printing a diagnostic if it's invalid or out of range
+fn spin_value<T: FromStr + PartialOrd + Display>(
+    name: &str,
+    value: &str,
+    min: T,
+    max: T,
+) -> Option<T> {
+    match value.parse::<T>() {
+        Ok(value) if value >= min && value <= max => Some(value),
+        _ => {
+            println!(
+                "info string invalid value {} for {}, expected {} to {}",
+                value, name, min, max
+            );
+            None
+        }
+    }
+}
+
+/// Parses a check option value, printing a diagnostic if it's invalid
+fn check_value(name: &str, value: &str) -> Option<bool> {
+    let parsed = value.to_lowercase().parse().ok();
+    if parsed.is_none() {
+        println!("info string invalid value {} for {}", value, name);
+    }
+    parsed
+}
+
 pub fn convert_move_to_uci(make_move: &mut Move, board: &Board, chess960: bool) {
     if !chess960 && board.color_on(make_move.from) == board.color_on(make_move.to) {
         let rights = board.castle_rights(board.side_to_move());
@@ -226,7 +506,7 @@ pub fn convert_move_to_uci(make_move: &mut Move, board: &Board, chess960: bool)
     }
 }
 
-fn convert_move(make_move: &mut Move, board: &Board, chess960: bool) {
+pub fn convert_move(make_move: &mut Move, board: &Board, chess960: bool) {
     let convert_castle = !chess960
         && board.piece_on(make_move.from) == Some(Piece::King)
         && make_move.from.file() == File::E
@@ -0,0 +1,130 @@
+use cozy_chess::Move;
+
+use crate::bm::bm_util::position::Position;
+
+use super::convert_move_to_uci;
+
+/// Counts leaf nodes of the legal move tree
+pub fn perft(position: &mut Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut moves = vec![];
+    position.board().generate_moves(|piece_moves| {
+        moves.extend(piece_moves);
+        false
+    });
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for make_move in moves {
+        position.make_move(make_move);
+        nodes += perft(position, depth - 1);
+        position.unmake_move();
+    }
+    nodes
+}
+
+/// [Perft](perft) split by root move, root moves are in UCI notation
+/// - Depth 0 has no root moves to split by, its single leaf is only counted by [perft]
+pub fn divide(position: &mut Position, depth: u32, chess960: bool) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return vec![];
+    }
+    let mut moves = vec![];
+    position.board().generate_moves(|piece_moves| {
+        moves.extend(piece_moves);
+        false
+    });
+    let board = position.board().clone();
+    moves
+        .into_iter()
+        .map(|make_move| {
+            position.make_move(make_move);
+            let nodes = perft(position, depth - 1);
+            position.unmake_move();
+
+            let mut uci_move = make_move;
+            convert_move_to_uci(&mut uci_move, &board, chess960);
+            (uci_move, nodes)
+        })
+        .collect()
+}
+
+/// Fen, whether it is a Chess960 position, depth and leaf node count
+#[cfg(test)]
+const POSITIONS: &[(&str, bool, u32, u64)] = &[
+    (
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        false,
+        4,
+        197281,
+    ),
+    (
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        false,
+        3,
+        97862,
+    ),
+    ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", false, 4, 43238),
+    (
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        false,
+        3,
+        9467,
+    ),
+    (
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        false,
+        3,
+        62379,
+    ),
+    (
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        false,
+        3,
+        89890,
+    ),
+    (
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        true,
+        3,
+        12189,
+    ),
+    (
+        "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        true,
+        3,
+        18002,
+    ),
+];
+
+#[test]
+fn perft_positions() {
+    use super::convert_move;
+    use cozy_chess::Board;
+
+    for &(fen, chess960, depth, expected) in POSITIONS {
+        let board = Board::from_fen(fen, chess960).unwrap();
+        let mut position = Position::new(board.clone());
+        let divided = divide(&mut position, depth, chess960);
+        assert_eq!(position.board(), &board);
+        assert_eq!(
+            divided.iter().map(|(_, nodes)| nodes).sum::<u64>(),
+            expected,
+            "{}",
+            fen
+        );
+
+        for (uci_move, _) in divided {
+            let mut make_move = uci_move.to_string().parse().unwrap();
+            convert_move(&mut make_move, &board, chess960);
+            assert!(board.is_legal(make_move), "{} {}", fen, uci_move);
+        }
+    }
+
+    let mut position = Position::new(Board::default());
+    assert!(divide(&mut position, 0, false).is_empty());
+    assert_eq!(perft(&mut position, 0), 1);
+}
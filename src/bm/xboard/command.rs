@@ -0,0 +1,194 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub enum XBoardCommand {
+    XBoard,
+    ProtoVer(u32),
+    New,
+    Variant(String),
+    SetBoard(String),
+    /// Move in coordinate notation or `O-O`/`O-O-O`, validated against the board by the adapter
+    UserMove(String),
+    Go,
+    Force,
+    Level {
+        moves_per_control: u32,
+        base: Duration,
+        inc: Duration,
+    },
+    St(Duration),
+    Sd(u32),
+    Time(Duration),
+    OTim(Duration),
+    Post,
+    NoPost,
+    Analyze,
+    Exit,
+    MoveNow,
+    Ping(u32),
+    Empty,
+    Quit,
+}
+
+#[derive(Debug, Clone)]
+pub enum XBoardParseError {
+    Unknown,
+    /// Parameter given without a value
+    MissingValue(&'static str),
+    /// Parameter given with a value that can't be parsed
+    InvalidValue(&'static str, String),
+}
+
+impl Display for XBoardParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XBoardParseError::Unknown => write!(f, "unknown command"),
+            XBoardParseError::MissingValue(name) => write!(f, "missing value for {}", name),
+            XBoardParseError::InvalidValue(name, value) => {
+                write!(f, "invalid value {} for {}", value, name)
+            }
+        }
+    }
+}
+
+fn parse_value<'a, T: FromStr>(
+    split: &mut impl Iterator<Item = &'a str>,
+    name: &'static str,
+) -> Result<T, XBoardParseError> {
+    let value = split.next().ok_or(XBoardParseError::MissingValue(name))?;
+    value
+        .parse()
+        .map_err(|_| XBoardParseError::InvalidValue(name, value.to_string()))
+}
+
+/// Parses fractional seconds, negative, infinite and out of range values are invalid
+fn parse_seconds<'a>(
+    split: &mut impl Iterator<Item = &'a str>,
+    name: &'static str,
+) -> Result<Duration, XBoardParseError> {
+    let value = split.next().ok_or(XBoardParseError::MissingValue(name))?;
+    let invalid = || XBoardParseError::InvalidValue(name, value.to_string());
+    let seconds = value.parse::<f64>().map_err(|_| invalid())?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(invalid());
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// Parses centiseconds, clocks may be negative once the flag has fallen
+fn parse_centis<'a>(
+    split: &mut impl Iterator<Item = &'a str>,
+    name: &'static str,
+) -> Result<Duration, XBoardParseError> {
+    let centis = parse_value::<i64>(split, name)?;
+    Ok(Duration::from_millis(centis.max(0) as u64 * 10))
+}
+
+/// Parses the `level` base time given as `minutes` or `minutes:seconds`
+fn parse_base<'a>(split: &mut impl Iterator<Item = &'a str>) -> Result<Duration, XBoardParseError> {
+    let value = split
+        .next()
+        .ok_or(XBoardParseError::MissingValue("level"))?;
+    let invalid = || XBoardParseError::InvalidValue("level", value.to_string());
+    let (minutes, seconds) = value.split_once(':').unwrap_or((value, "0"));
+    let minutes = minutes.parse::<u64>().map_err(|_| invalid())?;
+    let seconds = seconds.parse::<u64>().map_err(|_| invalid())?;
+    Ok(Duration::from_secs(minutes * 60 + seconds))
+}
+
+/// Whether the token looks like a move xboard sends without the `usermove` prefix
+fn is_move(token: &str) -> bool {
+    matches!(token, "O-O" | "O-O-O") || token.parse::<cozy_chess::Move>().is_ok()
+}
+
+impl XBoardCommand {
+    pub fn parse(input: &str) -> Result<Self, XBoardParseError> {
+        let mut split = input.split_ascii_whitespace();
+        let token = match split.next() {
+            Some(string) => string,
+            None => return Ok(XBoardCommand::Empty),
+        };
+        Ok(match token {
+            "xboard" => XBoardCommand::XBoard,
+            "protover" => XBoardCommand::ProtoVer(parse_value(&mut split, "protover")?),
+            "new" => XBoardCommand::New,
+            "variant" => XBoardCommand::Variant(parse_value(&mut split, "variant")?),
+            "setboard" => {
+                let fen = split.collect::<Vec<_>>().join(" ");
+                if fen.is_empty() {
+                    return Err(XBoardParseError::MissingValue("setboard"));
+                }
+                XBoardCommand::SetBoard(fen)
+            }
+            "usermove" => XBoardCommand::UserMove(parse_value(&mut split, "usermove")?),
+            "go" => XBoardCommand::Go,
+            "force" => XBoardCommand::Force,
+            "level" => XBoardCommand::Level {
+                moves_per_control: parse_value(&mut split, "level")?,
+                base: parse_base(&mut split)?,
+                inc: parse_seconds(&mut split, "level")?,
+            },
+            "st" => XBoardCommand::St(parse_seconds(&mut split, "st")?),
+            "sd" => XBoardCommand::Sd(parse_value(&mut split, "sd")?),
+            "time" => XBoardCommand::Time(parse_centis(&mut split, "time")?),
+            "otim" => XBoardCommand::OTim(parse_centis(&mut split, "otim")?),
+            "post" => XBoardCommand::Post,
+            "nopost" => XBoardCommand::NoPost,
+            "analyze" => XBoardCommand::Analyze,
+            "exit" => XBoardCommand::Exit,
+            "?" => XBoardCommand::MoveNow,
+            "ping" => XBoardCommand::Ping(parse_value(&mut split, "ping")?),
+            "quit" => XBoardCommand::Quit,
+            // Commands that don't affect the engine
+            "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name"
+            | "rating" | "result" | "." | "ics" | "draw" | "nps" => XBoardCommand::Empty,
+            token if is_move(token) => XBoardCommand::UserMove(token.to_string()),
+            _ => return Err(XBoardParseError::Unknown),
+        })
+    }
+}
+
+#[test]
+fn parse_commands() {
+    let invalid = [
+        "level 40 5:xx 0",
+        "level 40",
+        "st",
+        "st inf",
+        "st NaN",
+        "st -1",
+        "st 1e30",
+        "level 40 5 -0.5",
+        "sd deep",
+        "time",
+        "setboard",
+        "protover",
+        "bogus",
+    ];
+    for input in invalid {
+        assert!(XBoardCommand::parse(input).is_err(), "{}", input);
+    }
+    assert!(matches!(
+        XBoardCommand::parse("level 40 0:30 1.5"),
+        Ok(XBoardCommand::Level { moves_per_control: 40, base, inc })
+            if base == Duration::from_secs(30) && inc == Duration::from_millis(1500)
+    ));
+    assert!(matches!(
+        XBoardCommand::parse("level 0 2 0"),
+        Ok(XBoardCommand::Level { moves_per_control: 0, base, .. })
+            if base == Duration::from_secs(120)
+    ));
+    assert!(matches!(
+        XBoardCommand::parse("time -50"),
+        Ok(XBoardCommand::Time(time)) if time.is_zero()
+    ));
+    assert!(matches!(
+        XBoardCommand::parse("e7e8q"),
+        Ok(XBoardCommand::UserMove(mv)) if mv == "e7e8q"
+    ));
+    assert!(matches!(
+        XBoardCommand::parse("usermove O-O-O"),
+        Ok(XBoardCommand::UserMove(mv)) if mv == "O-O-O"
+    ));
+}
@@ -0,0 +1,466 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use cozy_chess::{Board, Color, Move, Piece, Rank, Square};
+
+use crate::bm::bm_runner::ab_runner::AbRunner;
+use crate::bm::bm_runner::config::{GuiInfo, NoInfo, Run, XBoardInfo};
+use crate::bm::bm_runner::time::{TimeManagementInfo, TimeManager};
+use crate::bm::uci::{convert_move, convert_move_to_uci};
+
+mod command;
+
+use command::XBoardCommand;
+
+const VERSION: &str = "9.0";
+
+/// Time control assumed until `level` or `st` is received
+const DEFAULT_MOVES_PER_CONTROL: u32 = 40;
+const DEFAULT_BASE: Duration = Duration::from_secs(300);
+
+enum ThreadReq {
+    Go(GoReq),
+    Quit,
+}
+
+struct GoReq {
+    bm_runner: Arc<Mutex<AbRunner>>,
+    time_manager: Arc<TimeManager>,
+    chess960: bool,
+    post: bool,
+    analyze: bool,
+    /// Value of [XBoardAdapter::generation] when the search was requested
+    generation: u32,
+}
+
+/// Chess Engine Communication Protocol adapter, the engine plays
+/// the moves it finds on its own board as xboard expects
+pub struct XBoardAdapter {
+    bm_runner: Arc<Mutex<AbRunner>>,
+    time_manager: Arc<TimeManager>,
+
+    sender: Sender<ThreadReq>,
+    /// Incremented by every stop, requests from an older generation are
+    /// dropped without searching and their moves are neither played nor sent
+    generation: Arc<AtomicU32>,
+    /// Side the engine plays, [None] in force mode
+    engine_color: Option<Color>,
+    analyzing: bool,
+    post: bool,
+    chess960: bool,
+
+    moves_per_control: u32,
+    /// Ply of the position set by `new` or `setboard`, time controls count moves from there
+    start_ply: u32,
+    base: Duration,
+    inc: Duration,
+    move_time: Option<Duration>,
+    max_depth: Option<u32>,
+    time: Option<Duration>,
+    opponent_time: Option<Duration>,
+}
+
+impl XBoardAdapter {
+    pub fn new() -> Self {
+        let time_manager = Arc::new(TimeManager::new());
+        let bm_runner = Arc::new(Mutex::new(AbRunner::new(
+            Board::default(),
+            time_manager.clone(),
+        )));
+
+        let generation = Arc::new(AtomicU32::new(0));
+        let (tx, rx): (Sender<ThreadReq>, Receiver<ThreadReq>) = mpsc::channel();
+        let current = generation.clone();
+        std::thread::spawn(move || loop {
+            if let Ok(req) = rx.recv() {
+                match req {
+                    ThreadReq::Go(req) => {
+                        let stale = || req.generation != current.load(Ordering::SeqCst);
+                        let mut bm_runner = req.bm_runner.lock().unwrap();
+                        if stale() {
+                            continue;
+                        }
+                        let gui_info: Arc<dyn GuiInfo> = match req.post {
+                            true => Arc::new(XBoardInfo),
+                            false => Arc::new(NoInfo),
+                        };
+                        let (best_move, _, _, _) = bm_runner.search::<Run>(gui_info);
+                        req.time_manager.wait_for_stop();
+                        if req.analyze || stale() {
+                            continue;
+                        }
+                        let notation = to_xboard(best_move, bm_runner.get_board(), req.chess960);
+                        bm_runner.make_move(best_move);
+                        println!("move {}", notation);
+                    }
+                    ThreadReq::Quit => {
+                        return;
+                    }
+                }
+            }
+        });
+        Self {
+            bm_runner,
+            time_manager,
+            sender: tx,
+            generation,
+            engine_color: Some(Color::Black),
+            analyzing: false,
+            post: false,
+            chess960: false,
+            moves_per_control: DEFAULT_MOVES_PER_CONTROL,
+            start_ply: 0,
+            base: DEFAULT_BASE,
+            inc: Duration::ZERO,
+            move_time: None,
+            max_depth: None,
+            time: None,
+            opponent_time: None,
+        }
+    }
+
+    pub fn input(&mut self, input: &str) -> bool {
+        let command = match XBoardCommand::parse(input) {
+            Ok(command) => command,
+            Err(err) => {
+                println!("Error ({}): {}", err, input);
+                return true;
+            }
+        };
+        match command {
+            XBoardCommand::XBoard | XBoardCommand::Empty => {}
+            // Protocol version 1 has no feature negotiation
+            XBoardCommand::ProtoVer(1) => {}
+            XBoardCommand::ProtoVer(_) => {
+                println!(
+                    "feature myname=\"Black Marlin {}\" setboard=1 usermove=1 ping=1 analyze=1 \
+                     colors=0 sigint=0 sigterm=0 reuse=1 variants=\"normal,fischerandom\" done=1",
+                    VERSION
+                );
+            }
+            XBoardCommand::New => {
+                self.stop();
+                self.analyzing = false;
+                self.chess960 = false;
+                self.engine_color = Some(Color::Black);
+                self.moves_per_control = DEFAULT_MOVES_PER_CONTROL;
+                self.start_ply = 0;
+                self.base = DEFAULT_BASE;
+                self.inc = Duration::ZERO;
+                self.move_time = None;
+                self.max_depth = None;
+                self.time = None;
+                self.opponent_time = None;
+                let runner = &mut *self.bm_runner.lock().unwrap();
+                runner.new_game();
+                runner.set_chess960(false);
+                runner.set_board(Board::default());
+            }
+            XBoardCommand::Variant(variant) => {
+                let chess960 = match variant.as_str() {
+                    "normal" => false,
+                    "fischerandom" => true,
+                    _ => {
+                        println!("Error (unsupported variant): {}", variant);
+                        return true;
+                    }
+                };
+                self.stop();
+                self.chess960 = chess960;
+                let runner = &mut *self.bm_runner.lock().unwrap();
+                runner.set_chess960(chess960);
+            }
+            XBoardCommand::SetBoard(fen) => {
+                let Some(board) = parse_fen(&fen) else {
+                    println!("tellusererror Illegal position");
+                    return true;
+                };
+                self.stop();
+                self.start_ply = ply(&board);
+                self.bm_runner.lock().unwrap().set_board(board);
+                self.restart_analysis();
+            }
+            XBoardCommand::UserMove(notation) => {
+                self.stop();
+                {
+                    let runner = &mut *self.bm_runner.lock().unwrap();
+                    let Some(make_move) = from_xboard(&notation, runner.get_board(), self.chess960)
+                    else {
+                        println!("Illegal move: {}", notation);
+                        return true;
+                    };
+                    runner.make_move(make_move);
+                }
+                if self.analyzing {
+                    self.restart_analysis();
+                } else if self.engine_color == Some(self.side_to_move()) {
+                    self.think();
+                }
+            }
+            XBoardCommand::Go => {
+                self.stop();
+                self.analyzing = false;
+                self.engine_color = Some(self.side_to_move());
+                self.think();
+            }
+            XBoardCommand::Force => {
+                self.stop();
+                self.engine_color = None;
+            }
+            XBoardCommand::Level {
+                moves_per_control,
+                base,
+                inc,
+            } => {
+                self.moves_per_control = moves_per_control;
+                self.base = base;
+                self.inc = inc;
+                self.move_time = None;
+                self.time = None;
+                self.opponent_time = None;
+            }
+            XBoardCommand::St(move_time) => self.move_time = Some(move_time),
+            XBoardCommand::Sd(depth) => self.max_depth = Some(depth),
+            XBoardCommand::Time(time) => self.time = Some(time),
+            XBoardCommand::OTim(time) => self.opponent_time = Some(time),
+            XBoardCommand::Post => self.post = true,
+            XBoardCommand::NoPost => self.post = false,
+            XBoardCommand::Analyze => {
+                self.stop();
+                self.analyzing = true;
+                self.engine_color = None;
+                self.restart_analysis();
+            }
+            XBoardCommand::Exit => {
+                self.stop();
+                self.analyzing = false;
+            }
+            XBoardCommand::MoveNow => {
+                if !self.analyzing {
+                    self.time_manager.abort_now();
+                }
+            }
+            XBoardCommand::Ping(id) => println!("pong {}", id),
+            XBoardCommand::Quit => {
+                self.stop();
+                self.sender.send(ThreadReq::Quit).unwrap();
+                return false;
+            }
+        }
+        true
+    }
+
+    fn side_to_move(&self) -> Color {
+        self.bm_runner.lock().unwrap().get_board().side_to_move()
+    }
+
+    /// Aborts the running search without playing its move and waits for it to finish,
+    /// searches that were requested but haven't started yet are dropped
+    fn stop(&mut self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.time_manager.abort_now();
+        drop(self.bm_runner.lock().unwrap());
+    }
+
+    fn restart_analysis(&mut self) {
+        if self.analyzing {
+            self.start(&[TimeManagementInfo::Infinite]);
+        }
+    }
+
+    /// Searches for a move to play under the current time control
+    fn think(&mut self) {
+        let mut info = vec![];
+        if let Some(depth) = self.max_depth {
+            info.push(TimeManagementInfo::MaxDepth(depth));
+        }
+        if let Some(move_time) = self.move_time {
+            info.push(TimeManagementInfo::MoveTime(move_time));
+        } else {
+            let board = self.bm_runner.lock().unwrap().get_board().clone();
+            let time = self.time.unwrap_or(self.base);
+            let opponent_time = self.opponent_time.unwrap_or(self.base);
+            let (w_time, b_time) = match board.side_to_move() {
+                Color::White => (time, opponent_time),
+                Color::Black => (opponent_time, time),
+            };
+            info.extend([
+                TimeManagementInfo::WTime(w_time),
+                TimeManagementInfo::BTime(b_time),
+                TimeManagementInfo::WInc(self.inc),
+                TimeManagementInfo::BInc(self.inc),
+            ]);
+            if self.moves_per_control != 0 {
+                info.push(TimeManagementInfo::MovesToGo(moves_to_go(
+                    self.moves_per_control,
+                    self.start_ply,
+                    &board,
+                )));
+            }
+        }
+        self.start(&info);
+    }
+
+    fn start(&mut self, info: &[TimeManagementInfo]) {
+        {
+            let runner = &mut *self.bm_runner.lock().unwrap();
+            let mut game_over = true;
+            runner.get_board().generate_moves(|_| {
+                game_over = false;
+                true
+            });
+            if game_over {
+                return;
+            }
+            self.time_manager.initiate(runner.get_board(), info);
+        }
+        let req = GoReq {
+            bm_runner: self.bm_runner.clone(),
+            time_manager: self.time_manager.clone(),
+            chess960: self.chess960,
+            post: self.post || self.analyzing,
+            analyze: self.analyzing,
+            generation: self.generation.load(Ordering::SeqCst),
+        };
+        self.sender.send(ThreadReq::Go(req)).unwrap();
+    }
+}
+
+impl Default for XBoardAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Plies played since the start position, derived from the move counter
+fn ply(board: &Board) -> u32 {
+    (board.fullmove_number() as u32).saturating_sub(1) * 2
+        + (board.side_to_move() == Color::Black) as u32
+}
+
+/// Moves the side to move has left in the current `level` time control, counting
+/// from the position at `start_ply` as xboard does after `setboard`
+fn moves_to_go(moves_per_control: u32, start_ply: u32, board: &Board) -> u32 {
+    let played = ply(board).saturating_sub(start_ply) / 2;
+    moves_per_control - played % moves_per_control
+}
+
+/// Parses a FEN, Shredder-FEN or X-FEN where `KQkq` refer to the outermost rooks
+fn parse_fen(fen: &str) -> Option<Board> {
+    if let Ok(board) = fen.parse() {
+        return Some(board);
+    }
+    let fields = fen.split_ascii_whitespace().collect::<Vec<_>>();
+    let castling = *fields.get(2)?;
+    let mut without_castling = fields.clone();
+    without_castling[2] = "-";
+    let board = Board::from_fen(&without_castling.join(" "), false).ok()?;
+
+    let mut shredder = String::new();
+    for right in castling.chars().filter(|&right| right != '-') {
+        let color = match right.is_ascii_uppercase() {
+            true => Color::White,
+            false => Color::Black,
+        };
+        let king = board.king(color);
+        let rooks =
+            board.colored_pieces(color, Piece::Rook) & Rank::First.relative_to(color).bitboard();
+        let mut files = rooks.into_iter().map(|square| square.file());
+        let file = match right.to_ascii_lowercase() {
+            'k' => files.filter(|&file| file > king.file()).max(),
+            'q' => files.find(|&file| file < king.file()),
+            file => file.try_into().ok(),
+        }?;
+        let file = char::from(file);
+        shredder.push(match color {
+            Color::White => file.to_ascii_uppercase(),
+            Color::Black => file,
+        });
+    }
+    let mut fields = fields;
+    if !shredder.is_empty() {
+        fields[2] = &shredder;
+    }
+    Board::from_fen(&fields.join(" "), true).ok()
+}
+
+/// Converts a move in coordinate notation or `O-O`/`O-O-O` to a legal move
+fn from_xboard(notation: &str, board: &Board, chess960: bool) -> Option<Move> {
+    let make_move = match notation {
+        "O-O" | "O-O-O" => {
+            let king = board.king(board.side_to_move());
+            let rights = board.castle_rights(board.side_to_move());
+            let rook = match notation {
+                "O-O" => rights.short,
+                _ => rights.long,
+            }?;
+            Move {
+                from: king,
+                to: Square::new(rook, king.rank()),
+                promotion: None,
+            }
+        }
+        _ => {
+            let mut make_move = notation.parse().ok()?;
+            convert_move(&mut make_move, board, chess960);
+            make_move
+        }
+    };
+    board.is_legal(make_move).then_some(make_move)
+}
+
+/// Castling is sent as `O-O`/`O-O-O` in Chess960 and as the king move otherwise
+fn to_xboard(make_move: Move, board: &Board, chess960: bool) -> String {
+    if chess960 && board.colors(board.side_to_move()).has(make_move.to) {
+        return match make_move.to.file() > make_move.from.file() {
+            true => "O-O",
+            false => "O-O-O",
+        }
+        .to_string();
+    }
+    let mut make_move = make_move;
+    convert_move_to_uci(&mut make_move, board, chess960);
+    make_move.to_string()
+}
+
+#[test]
+fn move_notation() {
+    use cozy_chess::File;
+
+    let board = parse_fen("rk2r3/pppppppp/8/8/8/8/PPPPPPPP/RK2R3 w KQkq - 0 1").unwrap();
+    assert_eq!(board.castle_rights(Color::White).short, Some(File::E));
+    assert_eq!(board.castle_rights(Color::Black).long, Some(File::A));
+
+    let castle = from_xboard("O-O", &board, true).unwrap();
+    assert_eq!(castle.to, Square::E1);
+    assert_eq!(to_xboard(castle, &board, true), "O-O");
+    let castle = from_xboard("O-O-O", &board, true).unwrap();
+    assert_eq!(to_xboard(castle, &board, true), "O-O-O");
+
+    let board = Board::default();
+    let make_move = from_xboard("e2e4", &board, false).unwrap();
+    assert_eq!(to_xboard(make_move, &board, false), "e2e4");
+    assert!(from_xboard("e2e5", &board, false).is_none());
+    assert!(from_xboard("O-O", &board, false).is_none());
+
+    let board = parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let castle = from_xboard("e1g1", &board, false).unwrap();
+    assert_eq!(to_xboard(castle, &board, false), "e1g1");
+}
+
+#[test]
+fn time_control_moves() {
+    let board = Board::default();
+    assert_eq!(moves_to_go(40, 0, &board), 40);
+    let board = parse_fen("8/8/8/4k3/8/8/4K3/8 b - - 10 60").unwrap();
+    let start_ply = ply(&board);
+    assert_eq!(moves_to_go(40, start_ply, &board), 40);
+    let mut later = parse_fen("8/8/8/4k3/8/8/4K3/8 b - - 14 62").unwrap();
+    assert_eq!(moves_to_go(40, start_ply, &later), 38);
+    later = parse_fen("8/8/8/4k3/8/8/4K3/8 w - - 14 62").unwrap();
+    assert_eq!(moves_to_go(40, start_ply, &later), 39);
+    later = parse_fen("8/8/8/4k3/8/8/4K3/8 b - - 14 100").unwrap();
+    assert_eq!(moves_to_go(40, start_ply, &later), 40);
+}
@@ -0,0 +1,436 @@
+//! C interface to [Engine], see `include/blackmarlin.h`
+//!
+//! Moves are exchanged as null terminated UCI strings and every function
+//! returns 0 on success and -1 on failure unless documented otherwise
+
+use std::collections::VecDeque;
+use std::ffi::{c_char, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use cozy_chess::{Board, Move};
+
+use crate::bm::bm_runner::config::SearchInfo;
+use crate::bm::bm_util::t_table::Bounds;
+use crate::bm::engine::{Engine, SearchHandle, SearchLimits, SearchResult};
+
+/// Size of [BmInfo::pv] including the null terminator
+pub const BM_PV_LEN: usize = 1024;
+
+/// Engine handle created by [bm_engine_new]
+pub struct BmEngine {
+    engine: Engine,
+    root: Board,
+    moves: Vec<Move>,
+    chess960: bool,
+    search: Option<SearchHandle>,
+    /// Whether the running search only ends once it's stopped
+    infinite: bool,
+    /// Progress reports of a finished search that haven't been polled
+    pending: VecDeque<SearchInfo>,
+    result: Option<SearchResult>,
+}
+
+/// Search limits, negative values leave a limit unset
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BmLimits {
+    pub depth: i32,
+    pub nodes: i64,
+    pub move_time_ms: i64,
+    pub w_time_ms: i64,
+    pub b_time_ms: i64,
+    pub w_inc_ms: i64,
+    pub b_inc_ms: i64,
+    pub moves_to_go: i32,
+    pub mate: i32,
+    /// Non zero to search until [bm_engine_stop] is called
+    pub infinite: i32,
+}
+
+/// Progress of a single PV line
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BmInfo {
+    pub depth: u32,
+    pub sel_depth: u32,
+    /// 1 based index of the PV line
+    pub multi_pv: u32,
+    /// Centipawns, or moves until mate if `is_mate` is non zero
+    pub score: i32,
+    pub is_mate: i32,
+    /// 0 for an exact score, 1 for a lower bound and 2 for an upper bound
+    pub bound: i32,
+    pub nodes: u64,
+    pub time_ms: u64,
+    /// Permille of the transposition table in use
+    pub hashfull: u32,
+    /// Space separated UCI moves
+    pub pv: [c_char; BM_PV_LEN],
+}
+
+fn duration(ms: i64) -> Option<Duration> {
+    (ms >= 0).then(|| Duration::from_millis(ms as u64))
+}
+
+fn positive<T: TryFrom<i64>>(value: i64) -> Option<T> {
+    (value >= 0).then(|| T::try_from(value).ok()).flatten()
+}
+
+impl BmLimits {
+    fn search_limits(&self) -> SearchLimits {
+        SearchLimits {
+            depth: positive(self.depth as i64),
+            nodes: positive(self.nodes),
+            move_time: duration(self.move_time_ms),
+            w_time: duration(self.w_time_ms),
+            b_time: duration(self.b_time_ms),
+            w_inc: duration(self.w_inc_ms),
+            b_inc: duration(self.b_inc_ms),
+            moves_to_go: positive(self.moves_to_go as i64),
+            mate: positive(self.mate as i64),
+            infinite: self.infinite != 0,
+            search_moves: vec![],
+        }
+    }
+}
+
+/// Copies a string into a C buffer, truncating it to fit
+fn write_str(string: &str, buffer: &mut [c_char]) {
+    let len = string.len().min(buffer.len() - 1);
+    for (out, &byte) in buffer.iter_mut().zip(&string.as_bytes()[..len]) {
+        *out = byte as c_char;
+    }
+    buffer[len] = 0;
+}
+
+impl From<&SearchInfo> for BmInfo {
+    fn from(info: &SearchInfo) -> Self {
+        let (score, is_mate) = match info.eval.mate_in() {
+            Some(mate_in) => (mate_in as i32, 1),
+            None => (info.eval.raw() as i32, 0),
+        };
+        let pv = info
+            .pv
+            .iter()
+            .map(|make_move| make_move.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut bm_info = BmInfo {
+            depth: info.depth,
+            sel_depth: info.sel_depth,
+            multi_pv: info.multi_pv as u32,
+            score,
+            is_mate,
+            bound: match info.bounds {
+                Bounds::Exact => 0,
+                Bounds::LowerBound => 1,
+                Bounds::UpperBound => 2,
+            },
+            nodes: info.nodes,
+            time_ms: info.elapsed.as_millis() as u64,
+            hashfull: info.hashfull,
+            pv: [0; BM_PV_LEN],
+        };
+        write_str(&pv, &mut bm_info.pv);
+        bm_info
+    }
+}
+
+impl BmEngine {
+    fn is_searching(&self) -> bool {
+        self.search
+            .as_ref()
+            .is_some_and(|search| !search.is_finished())
+    }
+
+    /// Waits for the running search and stores its result
+    /// - An infinite search is stopped first
+    /// - A panicking search leaves no result instead of unwinding into C
+    fn finish_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if self.infinite {
+                search.stop();
+            }
+            self.pending.extend(search.updates());
+            match panic::catch_unwind(AssertUnwindSafe(|| search.join())) {
+                Ok(result) => self.result = Some(result),
+                Err(_) => {
+                    self.result = None;
+                    self.reset_engine();
+                }
+            }
+        }
+    }
+
+    /// Replaces the engine after a panicking search, its state can't be trusted anymore
+    fn reset_engine(&mut self) {
+        self.engine = Engine::new();
+        self.engine.set_chess960(self.chess960);
+        if self
+            .engine
+            .set_position(self.root.clone(), &self.moves)
+            .is_err()
+        {
+            self.root = Board::default();
+            self.moves.clear();
+        }
+    }
+}
+
+/// Runs the body of an exported function, a panic returns `on_panic` instead of unwinding into C
+fn guard<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(on_panic)
+}
+
+/// Reads a null terminated UTF-8 string
+///
+/// # Safety
+/// `string` must be null or point to a null terminated string
+unsafe fn read_str<'a>(string: *const c_char) -> Option<&'a str> {
+    match string.is_null() {
+        true => None,
+        false => CStr::from_ptr(string).to_str().ok(),
+    }
+}
+
+/// Limits with every limit unset
+#[no_mangle]
+pub extern "C" fn bm_limits_default() -> BmLimits {
+    BmLimits {
+        depth: -1,
+        nodes: -1,
+        move_time_ms: -1,
+        w_time_ms: -1,
+        b_time_ms: -1,
+        w_inc_ms: -1,
+        b_inc_ms: -1,
+        moves_to_go: -1,
+        mate: -1,
+        infinite: 0,
+    }
+}
+
+/// Creates an engine set to the start position, free it with [bm_engine_free]
+/// - Returns null if the engine can't be created
+#[no_mangle]
+pub extern "C" fn bm_engine_new() -> *mut BmEngine {
+    guard(std::ptr::null_mut(), || {
+        Box::into_raw(Box::new(BmEngine {
+            engine: Engine::new(),
+            root: Board::default(),
+            moves: vec![],
+            chess960: false,
+            search: None,
+            infinite: false,
+            pending: VecDeque::new(),
+            result: None,
+        }))
+    })
+}
+
+/// Stops any running search and frees the engine
+///
+/// # Safety
+/// `engine` must be null or a pointer returned by [bm_engine_new] that hasn't been freed
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_free(engine: *mut BmEngine) {
+    if engine.is_null() {
+        return;
+    }
+    guard((), || {
+        let mut engine = Box::from_raw(engine);
+        if let Some(search) = &engine.search {
+            search.stop();
+        }
+        engine.finish_search();
+    })
+}
+
+/// Enables Chess960 castling notation and Shredder FENs, applies to following calls
+///
+/// # Safety
+/// `engine` must be a valid engine
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_set_chess960(engine: *mut BmEngine, chess960: i32) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return -1;
+    };
+    guard(-1, || {
+        engine.chess960 = chess960 != 0;
+        engine.engine.set_chess960(engine.chess960);
+        0
+    })
+}
+
+/// Sets the position, waits for a running search to finish
+///
+/// # Safety
+/// `engine` must be a valid engine and `fen` a null terminated string
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_set_fen(engine: *mut BmEngine, fen: *const c_char) -> i32 {
+    let (Some(engine), Some(fen)) = (engine.as_mut(), read_str(fen)) else {
+        return -1;
+    };
+    guard(-1, || {
+        let Ok(board) = Board::from_fen(fen.trim(), engine.chess960) else {
+            return -1;
+        };
+        engine.finish_search();
+        if engine.engine.set_position(board.clone(), &[]).is_err() {
+            return -1;
+        }
+        engine.root = board;
+        engine.moves.clear();
+        0
+    })
+}
+
+/// Plays a move in UCI notation, waits for a running search to finish
+///
+/// # Safety
+/// `engine` must be a valid engine and `uci_move` a null terminated string
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_make_move(
+    engine: *mut BmEngine,
+    uci_move: *const c_char,
+) -> i32 {
+    let (Some(engine), Some(uci_move)) = (engine.as_mut(), read_str(uci_move)) else {
+        return -1;
+    };
+    guard(-1, || {
+        let Ok(make_move) = uci_move.trim().parse::<Move>() else {
+            return -1;
+        };
+        engine.finish_search();
+        if engine.engine.make_move(make_move).is_err() {
+            return -1;
+        }
+        engine.moves.push(make_move);
+        0
+    })
+}
+
+/// Starts searching the current position in the background
+/// - Fails if a search is already running or the position has no legal moves
+///
+/// # Safety
+/// `engine` must be a valid engine and `limits` null or a valid pointer, null searches without limits
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_start_search(
+    engine: *mut BmEngine,
+    limits: *const BmLimits,
+) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return -1;
+    };
+    let limits = limits
+        .as_ref()
+        .copied()
+        .unwrap_or_else(|| bm_limits_default());
+    guard(-1, || {
+        if engine.is_searching() {
+            return -1;
+        }
+        engine.finish_search();
+        engine.pending.clear();
+        engine.result = None;
+        let limits = limits.search_limits();
+        let Ok(search) = engine.engine.search_async(&limits) else {
+            return -1;
+        };
+        engine.search = Some(search);
+        engine.infinite = limits.infinite;
+        0
+    })
+}
+
+/// Requests the running search to stop, the best move is still available
+///
+/// # Safety
+/// `engine` must be a valid engine
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_stop(engine: *mut BmEngine) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return -1;
+    };
+    guard(-1, || {
+        if let Some(search) = &engine.search {
+            search.stop();
+        }
+        0
+    })
+}
+
+/// Returns 1 if a search is running, otherwise 0
+///
+/// # Safety
+/// `engine` must be a valid engine
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_is_searching(engine: *const BmEngine) -> i32 {
+    guard(0, || {
+        engine
+            .as_ref()
+            .is_some_and(|engine| engine.is_searching())
+            .into()
+    })
+}
+
+/// Writes the oldest unread progress report into `info`
+/// - Returns 1 if a report was written and 0 if there was none
+///
+/// # Safety
+/// `engine` must be a valid engine and `info` a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_poll_info(engine: *mut BmEngine, info: *mut BmInfo) -> i32 {
+    let (Some(engine), Some(info)) = (engine.as_mut(), info.as_mut()) else {
+        return -1;
+    };
+    guard(-1, || {
+        let update = engine.pending.pop_front().or_else(|| {
+            engine
+                .search
+                .as_ref()
+                .and_then(|search| search.try_updates().next())
+        });
+        match update {
+            Some(update) => {
+                *info = BmInfo::from(&update);
+                1
+            }
+            None => 0,
+        }
+    })
+}
+
+/// Waits for the last search to finish and writes its best move into `buffer`
+/// - An infinite search is stopped first
+/// - Fails if no search was started, the search failed or the buffer is too small
+///
+/// # Safety
+/// `engine` must be a valid engine and `buffer` valid for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn bm_engine_best_move(
+    engine: *mut BmEngine,
+    buffer: *mut c_char,
+    len: usize,
+) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return -1;
+    };
+    if buffer.is_null() {
+        return -1;
+    }
+    guard(-1, || {
+        engine.finish_search();
+        let Some(result) = &engine.result else {
+            return -1;
+        };
+        let best_move = result.best_move.to_string();
+        if best_move.len() >= len {
+            return -1;
+        }
+        write_str(&best_move, std::slice::from_raw_parts_mut(buffer, len));
+        0
+    })
+}
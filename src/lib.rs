@@ -0,0 +1,11 @@
+mod bm;
+pub mod ffi;
+
+pub use bm::bm_runner::config::{GuiInfo, SearchInfo};
+pub use bm::bm_util::eval::Evaluation;
+pub use bm::bm_util::t_table::Bounds;
+pub use bm::engine::{Engine, EngineError, Score, SearchHandle, SearchLimits, SearchResult};
+pub use cozy_chess;
+
+#[doc(hidden)]
+pub use bm::bm_console::BmConsole;
@@ -1,6 +1,4 @@
-use crate::bm::bm_console::BmConsole;
-
-pub mod bm;
+use blackmarlin::BmConsole;
 
 fn main() {
     let mut bm_console = BmConsole::new();
@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const HEADER: &str = "include/blackmarlin.h";
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn header_is_current() {
+    let config = cbindgen::Config::from_file(manifest_dir().join("cbindgen.toml")).unwrap();
+    let bindings = cbindgen::Builder::new()
+        .with_crate(manifest_dir())
+        .with_config(config)
+        .generate()
+        .unwrap();
+    let mut generated = vec![];
+    bindings.write(&mut generated);
+    let generated = String::from_utf8(generated).unwrap();
+
+    let header_path = manifest_dir().join(HEADER);
+    if std::env::var_os("BLESS").is_some() {
+        std::fs::write(&header_path, &generated).unwrap();
+    }
+    let header = std::fs::read_to_string(&header_path).unwrap();
+    assert!(
+        header == generated,
+        "{} is out of date, regenerate it with `BLESS=1 cargo test --test ffi`",
+        HEADER
+    );
+}
+
+/// Directory containing the shared library built alongside this test
+fn library_dir() -> PathBuf {
+    let library = format!(
+        "{}blackmarlin{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    let exe = std::env::current_exe().unwrap();
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .find(|dir| dir.join(&library).exists())
+        .expect("shared library wasn't built")
+        .to_path_buf()
+}
+
+#[cfg(unix)]
+#[test]
+fn c_program() {
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("engine_test");
+    let lib_dir = library_dir();
+    let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(manifest_dir().join("tests/ffi/engine_test.c"))
+        .arg("-I")
+        .arg(manifest_dir().join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lblackmarlin")
+        .arg("-o")
+        .arg(&out)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success());
+
+    let output = Command::new(&out).output().unwrap();
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,74 @@
+#include <stdio.h>
+#include <string.h>
+
+#include "blackmarlin.h"
+
+#define CHECK(condition)                                                       \
+  do {                                                                         \
+    if (!(condition)) {                                                        \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,         \
+              #condition);                                                     \
+      return 1;                                                                \
+    }                                                                          \
+  } while (0)
+
+int main(void) {
+  BmEngine *engine = bm_engine_new();
+  CHECK(engine != NULL);
+
+  CHECK(bm_engine_set_fen(engine, "not a fen") == -1);
+  CHECK(bm_engine_set_fen(
+            engine,
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3") ==
+        0);
+  CHECK(bm_engine_make_move(engine, "f1c4") == 0);
+  CHECK(bm_engine_make_move(engine, "e1e3") == -1);
+  CHECK(bm_engine_make_move(engine, "g8f6") == 0);
+
+  char best_move[8];
+  CHECK(bm_engine_best_move(engine, best_move, sizeof(best_move)) == -1);
+
+  BmLimits limits = bm_limits_default();
+  limits.depth = 5;
+  CHECK(bm_engine_start_search(engine, &limits) == 0);
+  CHECK(bm_engine_best_move(engine, best_move, sizeof(best_move)) == 0);
+  CHECK(bm_engine_is_searching(engine) == 0);
+  printf("bestmove %s\n", best_move);
+  CHECK(strlen(best_move) >= 4);
+
+  BmInfo info;
+  unsigned int last_depth = 0;
+  while (bm_engine_poll_info(engine, &info) == 1) {
+    printf("depth %u score %d pv %s\n", info.depth, info.score, info.pv);
+    last_depth = info.depth;
+  }
+  CHECK(last_depth == 5);
+  CHECK(strncmp(info.pv, best_move, strlen(best_move)) == 0);
+
+  limits = bm_limits_default();
+  limits.infinite = 1;
+  CHECK(bm_engine_start_search(engine, &limits) == 0);
+  CHECK(bm_engine_start_search(engine, &limits) == -1);
+  CHECK(bm_engine_is_searching(engine) == 1);
+  CHECK(bm_engine_stop(engine) == 0);
+  CHECK(bm_engine_best_move(engine, best_move, sizeof(best_move)) == 0);
+  CHECK(bm_engine_make_move(engine, best_move) == 0);
+
+  // Infinite searches are stopped when waiting for the best move
+  CHECK(bm_engine_start_search(engine, &limits) == 0);
+  CHECK(bm_engine_best_move(engine, best_move, sizeof(best_move)) == 0);
+
+  // Checkmate and stalemate can't be searched
+  CHECK(bm_engine_set_fen(
+            engine,
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3") ==
+        0);
+  CHECK(bm_engine_start_search(engine, NULL) == -1);
+  CHECK(bm_engine_best_move(engine, best_move, sizeof(best_move)) == -1);
+  CHECK(bm_engine_set_fen(engine, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1") == 0);
+  CHECK(bm_engine_start_search(engine, &limits) == -1);
+  CHECK(bm_engine_is_searching(engine) == 0);
+
+  bm_engine_free(engine);
+  return 0;
+}